@@ -0,0 +1,3 @@
+fn main() {
+    luajit_src::Build::new().build().print_cargo_metadata();
+}
@@ -0,0 +1,4 @@
+//! Links a static LuaJIT into libinsolence's test binaries.
+//!
+//! The library itself is built in mlua's `module` mode and takes its Lua symbols from the game at
+//! load time, so unit tests that create a `Lua` need a runtime of their own.
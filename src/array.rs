@@ -0,0 +1,433 @@
+use crate::tbl::{Selector, as_number, seq_index};
+use mlua::prelude::*;
+
+// How nil gaps in an array are treated:
+// "stop" reads up to the first nil like `ipairs`, "skip" (the default) reads every index up to
+// the highest one and drops the nils, "keep" preserves them and records the length in `n` like
+// `table.pack`, and "error" refuses sparse input.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Holes {
+    Stop,
+    Skip,
+    Keep,
+    Error,
+}
+
+impl Holes {
+    pub(crate) fn from_opts(opts: Option<&LuaTable>) -> LuaResult<Self> {
+        let Some(opts) = opts else {
+            return Ok(Holes::Skip);
+        };
+
+        match opts.get::<Option<String>>("holes")?.as_deref() {
+            None | Some("skip") => Ok(Holes::Skip),
+            Some("stop") => Ok(Holes::Stop),
+            Some("keep") => Ok(Holes::Keep),
+            Some("error") => Ok(Holes::Error),
+            Some(other) => Err(mlua::Error::RuntimeError(format!(
+                "[INSOLENCE] Error: unknown holes option {}",
+                other
+            ))),
+        }
+    }
+}
+
+// Longest array `holes = "keep"` will fill with nils, so a stray `[1e9]` key can't allocate
+// a billion slots.
+const MAX_KEPT_LEN: usize = 1 << 24;
+
+// The array-index keys actually present, in order. Only walks real keys, so sparse tables with
+// huge indices stay cheap.
+fn indexed_entries(tbl: &LuaTable) -> LuaResult<Vec<(usize, LuaValue)>> {
+    let mut entries = Vec::new();
+
+    for pair in tbl.pairs::<LuaValue, LuaValue>() {
+        let (k, v) = pair?;
+        if let Some(idx) = seq_index(&k) {
+            entries.push((idx, v));
+        }
+    }
+    entries.sort_unstable_by_key(|(idx, _)| *idx);
+
+    Ok(entries)
+}
+
+pub(crate) fn read_array(tbl: &LuaTable, holes: Holes) -> LuaResult<Vec<LuaValue>> {
+    let mut values = Vec::new();
+
+    if holes == Holes::Stop {
+        for value in tbl.sequence_values::<LuaValue>() {
+            values.push(value?);
+        }
+        return Ok(values);
+    }
+
+    let entries = indexed_entries(tbl)?;
+    let len = match (holes, as_number(&tbl.raw_get::<LuaValue>("n")?)) {
+        (Holes::Keep, Some(n)) => n.max(0.0) as usize,
+        _ => entries.last().map_or(0, |(idx, _)| *idx),
+    };
+
+    match holes {
+        Holes::Error => {
+            // Sorted indices without holes are exactly 1, 2, 3, ...; the first mismatch is a hole.
+            if let Some(pos) = entries
+                .iter()
+                .enumerate()
+                .position(|(pos, (idx, _))| *idx != pos + 1)
+            {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "[INSOLENCE] Error: array has a hole at index {}",
+                    pos + 1
+                )));
+            }
+        }
+        Holes::Keep if len > MAX_KEPT_LEN => {
+            return Err(mlua::Error::RuntimeError(format!(
+                "[INSOLENCE] Error: array length {} is too large to keep its holes",
+                len
+            )));
+        }
+        _ => {}
+    }
+
+    for (idx, value) in entries {
+        if idx > len {
+            break;
+        }
+        if holes == Holes::Keep {
+            values.resize(idx - 1, LuaValue::Nil);
+        }
+        values.push(value);
+    }
+    if holes == Holes::Keep {
+        values.resize(len, LuaValue::Nil);
+    }
+
+    Ok(values)
+}
+
+pub(crate) fn write_array(lua: &Lua, values: Vec<LuaValue>, holes: Holes) -> LuaResult<LuaTable> {
+    let tbl = lua.create_table_with_capacity(values.len(), 0)?;
+    fill_array(&tbl, values, holes)?;
+
+    Ok(tbl)
+}
+
+fn fill_array(tbl: &LuaTable, values: Vec<LuaValue>, holes: Holes) -> LuaResult<()> {
+    let len = values.len();
+
+    for (idx, value) in values.into_iter().enumerate() {
+        if !value.is_nil() {
+            tbl.raw_set(idx + 1, value)?;
+        }
+    }
+
+    if holes == Holes::Keep {
+        tbl.raw_set("n", len)?;
+    }
+
+    Ok(())
+}
+
+// Replaces the array part of `tbl` in place, leaving its other keys alone.
+fn rewrite_array(tbl: &LuaTable, values: Vec<LuaValue>, holes: Holes) -> LuaResult<()> {
+    for (idx, _) in indexed_entries(tbl)? {
+        tbl.raw_set(idx, LuaValue::Nil)?;
+    }
+
+    fill_array(tbl, values, holes)
+}
+
+// Lua-style position: negative values count back from the end, so -1 is the last element.
+fn resolve_index(idx: i64, len: usize) -> i64 {
+    if idx < 0 { len as i64 + 1 + idx } else { idx }
+}
+
+fn positive_size(name: &str, size: i64) -> LuaResult<usize> {
+    if size < 1 {
+        return Err(mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: {} must be at least 1, got {}",
+            name, size
+        )));
+    }
+
+    Ok(size as usize)
+}
+
+pub(crate) fn slice(
+    lua: &Lua,
+    (tbl, start, finish, opts): (LuaTable, Option<i64>, Option<i64>, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let holes = Holes::from_opts(opts.as_ref())?;
+    let values = read_array(&tbl, holes)?;
+    let len = values.len();
+
+    let start = resolve_index(start.unwrap_or(1), len).max(1);
+    let finish = resolve_index(finish.unwrap_or(len as i64), len).min(len as i64);
+
+    let sliced = if start > finish {
+        Vec::new()
+    } else {
+        values[(start - 1) as usize..finish as usize].to_vec()
+    };
+
+    write_array(lua, sliced, holes)
+}
+
+pub(crate) fn chunk(
+    lua: &Lua,
+    (tbl, size, opts): (LuaTable, i64, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let holes = Holes::from_opts(opts.as_ref())?;
+    let size = positive_size("chunk size", size)?;
+    let values = read_array(&tbl, holes)?;
+    let result = lua.create_table()?;
+
+    for piece in values.chunks(size) {
+        result.raw_push(write_array(lua, piece.to_vec(), holes)?)?;
+    }
+
+    Ok(result)
+}
+
+pub(crate) fn sliding_window(
+    lua: &Lua,
+    (tbl, size, step, opts): (LuaTable, i64, Option<i64>, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let holes = Holes::from_opts(opts.as_ref())?;
+    let size = positive_size("window size", size)?;
+    let step = positive_size("window step", step.unwrap_or(1))?;
+    let values = read_array(&tbl, holes)?;
+    let result = lua.create_table()?;
+
+    let mut start = 0;
+    while start + size <= values.len() {
+        result.raw_push(write_array(
+            lua,
+            values[start..start + size].to_vec(),
+            holes,
+        )?)?;
+        start += step;
+    }
+
+    Ok(result)
+}
+
+// Only tables whose keys are all array indices are flattened; cards and other objects stay whole.
+fn is_flattenable(tbl: &LuaTable) -> LuaResult<bool> {
+    for pair in tbl.pairs::<LuaValue, LuaValue>() {
+        let (k, _) = pair?;
+        if seq_index(&k).is_none() {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+fn flatten_into(
+    values: Vec<LuaValue>,
+    depth: usize,
+    holes: Holes,
+    out: &mut Vec<LuaValue>,
+) -> LuaResult<()> {
+    for value in values {
+        match &value {
+            LuaValue::Table(inner) if depth > 0 && is_flattenable(inner)? => {
+                flatten_into(read_array(inner, holes)?, depth - 1, holes, out)?;
+            }
+            _ => out.push(value),
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn flatten(
+    lua: &Lua,
+    (tbl, depth, opts): (LuaTable, Option<usize>, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let holes = Holes::from_opts(opts.as_ref())?;
+    let mut out = Vec::new();
+    flatten_into(
+        read_array(&tbl, holes)?,
+        depth.unwrap_or(1),
+        holes,
+        &mut out,
+    )?;
+
+    write_array(lua, out, holes)
+}
+
+pub(crate) fn unique(
+    lua: &Lua,
+    (tbl, key, opts): (LuaTable, LuaValue, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let holes = Holes::from_opts(opts.as_ref())?;
+    let selector = match key {
+        LuaValue::Nil => None,
+        other => Some(Selector::from_lua(other)?),
+    };
+
+    // Identities are keys of a Lua table, so they compare the way table keys do (1 and 1.0 are the
+    // same). nil can't be a key and is tracked on its own; NaN never equals itself, so it is kept.
+    let seen = lua.create_table()?;
+    let mut seen_nil = false;
+    let mut kept = Vec::new();
+    for (idx, value) in read_array(&tbl, holes)?.into_iter().enumerate() {
+        let identity = match &selector {
+            Some(selector) => selector.select(&value, &LuaValue::Integer(idx as i64 + 1))?,
+            None => value.clone(),
+        };
+
+        let fresh = match identity {
+            LuaValue::Nil => !std::mem::replace(&mut seen_nil, true),
+            LuaValue::Number(n) if n.is_nan() => true,
+            identity => {
+                let fresh = seen.raw_get::<LuaValue>(identity.clone())?.is_nil();
+                seen.raw_set(identity, true)?;
+                fresh
+            }
+        };
+
+        if fresh {
+            kept.push(value);
+        }
+    }
+
+    write_array(lua, kept, holes)
+}
+
+// Positive counts move elements toward the end, wrapping around: rotate({1, 2, 3}, 1) is {3, 1, 2}.
+pub(crate) fn rotate(
+    lua: &Lua,
+    (tbl, count, opts): (LuaTable, i64, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let holes = Holes::from_opts(opts.as_ref())?;
+    let mut values = read_array(&tbl, holes)?;
+
+    if !values.is_empty() {
+        let shift = count.rem_euclid(values.len() as i64) as usize;
+        values.rotate_right(shift);
+    }
+
+    write_array(lua, values, holes)
+}
+
+pub(crate) fn interleave(lua: &Lua, tbls: LuaVariadic<LuaTable>) -> LuaResult<LuaTable> {
+    let arrays = tbls
+        .iter()
+        .map(|tbl| read_array(tbl, Holes::Skip))
+        .collect::<LuaResult<Vec<_>>>()?;
+    let longest = arrays.iter().map(Vec::len).max().unwrap_or(0);
+    let result = lua.create_table()?;
+
+    for idx in 0..longest {
+        for array in &arrays {
+            if let Some(value) = array.get(idx) {
+                result.raw_push(value.clone())?;
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+pub(crate) fn insert_at(
+    _: &Lua,
+    (tbl, index, value, opts): (LuaTable, i64, LuaValue, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let holes = Holes::from_opts(opts.as_ref())?;
+    let spread = match &opts {
+        Some(opts) => opts.get::<Option<bool>>("spread")?.unwrap_or(false),
+        None => false,
+    };
+
+    let mut values = read_array(&tbl, holes)?;
+    let len = values.len();
+    let position = resolve_index(index, len + 1);
+
+    if position < 1 || position > len as i64 + 1 {
+        return Err(mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: insert position {} is out of range for an array of length {}",
+            index, len
+        )));
+    }
+
+    let inserted = match (spread, value) {
+        (true, LuaValue::Table(items)) => read_array(&items, holes)?,
+        (_, value) => vec![value],
+    };
+
+    let at = (position - 1) as usize;
+    values.splice(at..at, inserted);
+    rewrite_array(&tbl, values, holes)?;
+
+    Ok(tbl)
+}
+
+pub(crate) fn remove_where(
+    lua: &Lua,
+    (tbl, pred, opts): (LuaTable, LuaValue, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let holes = Holes::from_opts(opts.as_ref())?;
+    let pred = Selector::from_lua(pred)?;
+
+    let mut kept = Vec::new();
+    let mut removed = Vec::new();
+    for (idx, value) in read_array(&tbl, holes)?.into_iter().enumerate() {
+        if pred.test(&value, &LuaValue::Integer(idx as i64 + 1))? {
+            removed.push(value);
+        } else {
+            kept.push(value);
+        }
+    }
+
+    rewrite_array(&tbl, kept, holes)?;
+    write_array(lua, removed, holes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(lua: &Lua, holes: &str) -> Option<LuaTable> {
+        let opts = lua.create_table().unwrap();
+        opts.set("holes", holes).unwrap();
+        Some(opts)
+    }
+
+    #[test]
+    fn huge_indices_do_not_walk_every_slot() {
+        let lua = Lua::new();
+        let tbl: LuaTable = lua.load("return {[1e9] = 1, [2] = 2}").eval().unwrap();
+
+        let sliced = slice(&lua, (tbl.clone(), None, None, None)).unwrap();
+        assert_eq!(sliced.raw_len(), 2);
+        assert_eq!(sliced.raw_get::<i64>(1).unwrap(), 2);
+        assert_eq!(sliced.raw_get::<i64>(2).unwrap(), 1);
+
+        assert!(slice(&lua, (tbl.clone(), None, None, opts(&lua, "keep"))).is_err());
+        let err = slice(&lua, (tbl, None, None, opts(&lua, "error"))).unwrap_err();
+        assert!(err.to_string().contains("hole at index 1"), "{}", err);
+    }
+
+    #[test]
+    fn keep_pads_holes_with_nil() {
+        let lua = Lua::new();
+        let tbl: LuaTable = lua.load("return {1, nil, 3, n = 4}").eval().unwrap();
+
+        let values = read_array(&tbl, Holes::Keep).unwrap();
+        assert_eq!(values.len(), 4);
+        assert!(values[1].is_nil() && values[3].is_nil());
+        assert_eq!(values[2].as_i64(), Some(3));
+    }
+
+    #[test]
+    fn huge_float_keys_are_not_indices() {
+        assert_eq!(seq_index(&LuaValue::Number(1e300)), None);
+        assert_eq!(seq_index(&LuaValue::Number(f64::INFINITY)), None);
+        assert_eq!(seq_index(&LuaValue::Number(3.0)), Some(3));
+    }
+}
@@ -0,0 +1,347 @@
+use crate::serial::preview;
+use crate::tbl::{DeepCopier, MetatableMode, compare_values, stable_sort_by, values_equal};
+use mlua::prelude::*;
+use std::collections::HashSet;
+use std::ffi::c_void;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Op {
+    Add,
+    Remove,
+    Change,
+}
+
+impl Op {
+    fn name(self) -> &'static str {
+        match self {
+            Op::Add => "add",
+            Op::Remove => "remove",
+            Op::Change => "change",
+        }
+    }
+
+    fn parse(name: &str) -> LuaResult<Self> {
+        match name {
+            "add" => Ok(Op::Add),
+            "remove" => Ok(Op::Remove),
+            "change" => Ok(Op::Change),
+            other => Err(mlua::Error::RuntimeError(format!(
+                "[INSOLENCE] Error: unknown diff op {}",
+                other
+            ))),
+        }
+    }
+}
+
+struct Change {
+    op: Op,
+    keys: Vec<LuaValue>,
+    old: LuaValue,
+    new: LuaValue,
+}
+
+fn key_text(key: &LuaValue) -> String {
+    match key {
+        LuaValue::String(s) => s.to_string_lossy(),
+        LuaValue::Integer(_) | LuaValue::Number(_) | LuaValue::Boolean(_) => {
+            key.to_string().unwrap_or_default()
+        }
+        other => format!("<{}>", other.type_name()),
+    }
+}
+
+fn path_text(keys: &[LuaValue]) -> String {
+    if keys.is_empty() {
+        return "<root>".to_string();
+    }
+
+    keys.iter().map(key_text).collect::<Vec<_>>().join(".")
+}
+
+struct Differ {
+    tolerance: f64,
+    seen: HashSet<(*const c_void, *const c_void)>,
+    changes: Vec<Change>,
+}
+
+impl Differ {
+    fn record(&mut self, op: Op, keys: &[LuaValue], old: LuaValue, new: LuaValue) {
+        self.changes.push(Change {
+            op,
+            keys: keys.to_vec(),
+            old,
+            new,
+        });
+    }
+
+    fn values(&mut self, keys: &mut Vec<LuaValue>, a: LuaValue, b: LuaValue) -> LuaResult<()> {
+        match (&a, &b) {
+            (LuaValue::Nil, LuaValue::Nil) => Ok(()),
+            (LuaValue::Nil, _) => {
+                self.record(Op::Add, keys, a, b);
+                Ok(())
+            }
+            (_, LuaValue::Nil) => {
+                self.record(Op::Remove, keys, a, b);
+                Ok(())
+            }
+            (LuaValue::Table(x), LuaValue::Table(y)) => self.tables(keys, x, y),
+            _ => {
+                if !values_equal(&a, &b, self.tolerance)? {
+                    self.record(Op::Change, keys, a, b);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // Keys are visited in sorted order so the same pair of tables always yields the same diff.
+    fn tables(&mut self, keys: &mut Vec<LuaValue>, a: &LuaTable, b: &LuaTable) -> LuaResult<()> {
+        if a == b || !self.seen.insert((a.to_pointer(), b.to_pointer())) {
+            return Ok(());
+        }
+
+        let mut all_keys = Vec::new();
+        for pair in a.pairs::<LuaValue, LuaValue>() {
+            all_keys.push(pair?.0);
+        }
+        for pair in b.pairs::<LuaValue, LuaValue>() {
+            let (k, _) = pair?;
+            if a.raw_get::<LuaValue>(k.clone())?.is_nil() {
+                all_keys.push(k);
+            }
+        }
+
+        for k in stable_sort_by(all_keys, compare_values) {
+            let old = a.raw_get::<LuaValue>(k.clone())?;
+            let new = b.raw_get::<LuaValue>(k.clone())?;
+
+            keys.push(k);
+            self.values(keys, old, new)?;
+            keys.pop();
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn diff(
+    lua: &Lua,
+    (a, b, opts): (LuaValue, LuaValue, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let mut differ = Differ {
+        tolerance: 0.0,
+        seen: HashSet::new(),
+        changes: Vec::new(),
+    };
+
+    if let Some(opts) = &opts {
+        differ.tolerance = opts.get::<Option<f64>>("tolerance")?.unwrap_or(0.0);
+    }
+
+    differ.values(&mut Vec::new(), a, b)?;
+
+    let result = lua.create_table()?;
+    for change in differ.changes {
+        let entry = lua.create_table()?;
+        entry.set("op", change.op.name())?;
+        entry.set("path", path_text(&change.keys))?;
+        entry.set("keys", lua.create_sequence_from(change.keys)?)?;
+        entry.set("old", change.old)?;
+        entry.set("new", change.new)?;
+        result.raw_push(entry)?;
+    }
+
+    Ok(result)
+}
+
+fn read_change(entry: &LuaTable) -> LuaResult<Change> {
+    let op = Op::parse(&entry.get::<String>("op")?)?;
+
+    // `path` is only for display: its keys aren't escaped, so "a.b" or "3" can't be read back.
+    let Some(keys) = entry.get::<Option<LuaTable>>("keys")? else {
+        return Err(mlua::Error::RuntimeError(
+            "[INSOLENCE] Error: diff entry is missing its keys list".to_string(),
+        ));
+    };
+    let keys = keys
+        .sequence_values::<LuaValue>()
+        .collect::<LuaResult<_>>()?;
+
+    Ok(Change {
+        op,
+        keys,
+        old: entry.get("old")?,
+        new: entry.get("new")?,
+    })
+}
+
+fn conflict(keys: &[LuaValue], expected: &LuaValue, found: &LuaValue) -> mlua::Error {
+    mlua::Error::RuntimeError(format!(
+        "[INSOLENCE] Error: patch conflict at {}: expected {}, found {}",
+        path_text(keys),
+        preview(expected),
+        preview(found)
+    ))
+}
+
+// Walks to the table holding the last key, creating missing intermediate tables.
+fn parent_of(lua: &Lua, root: &LuaTable, keys: &[LuaValue]) -> LuaResult<LuaTable> {
+    let mut current = root.clone();
+
+    for (depth, key) in keys[..keys.len() - 1].iter().enumerate() {
+        current = match current.raw_get::<LuaValue>(key.clone())? {
+            LuaValue::Table(tbl) => tbl,
+            LuaValue::Nil => {
+                let tbl = lua.create_table()?;
+                current.raw_set(key.clone(), tbl.clone())?;
+                tbl
+            }
+            other => {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "[INSOLENCE] Error: cannot patch {}: {} is not a table (got {})",
+                    path_text(keys),
+                    path_text(&keys[..=depth]),
+                    other.type_name()
+                )));
+            }
+        };
+    }
+
+    Ok(current)
+}
+
+pub(crate) fn patch(
+    lua: &Lua,
+    (target, changes, opts): (LuaValue, LuaTable, Option<LuaTable>),
+) -> LuaResult<LuaValue> {
+    let mut invert = false;
+    let mut strict = false;
+
+    if let Some(opts) = &opts {
+        invert = opts.get::<Option<bool>>("invert")?.unwrap_or(false);
+        strict = opts.get::<Option<bool>>("strict")?.unwrap_or(false);
+    }
+
+    let mut parsed = Vec::new();
+    for entry in changes.sequence_values::<LuaTable>() {
+        parsed.push(read_change(&entry?)?);
+    }
+
+    // Inverting undoes the changes last-to-first with each op reversed.
+    if invert {
+        parsed.reverse();
+        for change in &mut parsed {
+            change.op = match change.op {
+                Op::Add => Op::Remove,
+                Op::Remove => Op::Add,
+                Op::Change => Op::Change,
+            };
+            std::mem::swap(&mut change.old, &mut change.new);
+        }
+    }
+
+    let mut copier = DeepCopier::new(lua, MetatableMode::Keep);
+    let mut root = target;
+
+    for change in parsed {
+        let new = match change.op {
+            Op::Remove => LuaValue::Nil,
+            _ => copier.copy_value(change.new)?,
+        };
+        let expected = match change.op {
+            Op::Add => LuaValue::Nil,
+            _ => change.old,
+        };
+
+        let Some(last) = change.keys.last() else {
+            if strict && !values_equal(&root, &expected, 0.0)? {
+                return Err(conflict(&change.keys, &expected, &root));
+            }
+            root = new;
+            continue;
+        };
+
+        let LuaValue::Table(root_tbl) = &root else {
+            return Err(mlua::Error::RuntimeError(format!(
+                "[INSOLENCE] Error: cannot patch {}: target is not a table (got {})",
+                path_text(&change.keys),
+                root.type_name()
+            )));
+        };
+
+        let parent = parent_of(lua, root_tbl, &change.keys)?;
+        if strict {
+            let found = parent.raw_get::<LuaValue>(last.clone())?;
+            if !values_equal(&found, &expected, 0.0)? {
+                return Err(conflict(&change.keys, &expected, &found));
+            }
+        }
+        parent.raw_set(last.clone(), new)?;
+    }
+
+    Ok(root)
+}
+
+pub(crate) fn format_diff(_: &Lua, changes: LuaTable) -> LuaResult<String> {
+    let mut lines = Vec::new();
+
+    for entry in changes.sequence_values::<LuaTable>() {
+        let change = read_change(&entry?)?;
+        let path = path_text(&change.keys);
+
+        lines.push(match change.op {
+            Op::Add => format!("+ {} = {}", path, preview(&change.new)),
+            Op::Remove => format!("- {} = {}", path, preview(&change.old)),
+            Op::Change => format!(
+                "~ {}: {} -> {}",
+                path,
+                preview(&change.old),
+                preview(&change.new)
+            ),
+        });
+    }
+
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patch_round_trips_awkward_keys() {
+        let lua = Lua::new();
+        let before: LuaValue = lua
+            .load(r#"return {["a.b"] = 1, ["3"] = 1, ["*"] = 1}"#)
+            .eval()
+            .unwrap();
+        let after: LuaValue = lua
+            .load(r#"return {["a.b"] = 2, ["3"] = 2, ["*"] = 2}"#)
+            .eval()
+            .unwrap();
+
+        let changes = diff(&lua, (before.clone(), after.clone(), None)).unwrap();
+        let patched = patch(&lua, (before, changes, None)).unwrap();
+        let patched = patched.as_table().unwrap();
+
+        assert_eq!(patched.raw_get::<i64>("a.b").unwrap(), 2);
+        assert_eq!(patched.raw_get::<i64>("3").unwrap(), 2);
+        assert_eq!(patched.raw_get::<i64>("*").unwrap(), 2);
+        assert!(patched.raw_get::<LuaValue>("a").unwrap().is_nil());
+        assert!(patched.raw_get::<LuaValue>(3).unwrap().is_nil());
+    }
+
+    #[test]
+    fn entries_without_keys_are_rejected() {
+        let lua = Lua::new();
+        let changes: LuaTable = lua
+            .load(r#"return {{op = "change", path = "a.b", old = 1, new = 2}}"#)
+            .eval()
+            .unwrap();
+
+        let target = LuaValue::Table(lua.create_table().unwrap());
+        assert!(patch(&lua, (target, changes.clone(), None)).is_err());
+        assert!(format_diff(&lua, changes).is_err());
+    }
+}
@@ -0,0 +1,326 @@
+use mlua::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Vec2 {
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Rect {
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    pub(crate) w: f64,
+    pub(crate) h: f64,
+}
+
+impl Vec2 {
+    pub(crate) fn new(x: f64, y: f64) -> Self {
+        Vec2 { x, y }
+    }
+
+    pub(crate) fn length(self) -> f64 {
+        self.x.hypot(self.y)
+    }
+
+    pub(crate) fn dot(self, other: Vec2) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    pub(crate) fn normalize(self) -> Self {
+        let len = self.length();
+
+        if len == 0.0 {
+            self
+        } else {
+            Vec2::new(self.x / len, self.y / len)
+        }
+    }
+
+    pub(crate) fn rotate(self, angle: f64, origin: Vec2) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        let dx = self.x - origin.x;
+        let dy = self.y - origin.y;
+
+        Vec2::new(
+            origin.x + dx * cos - dy * sin,
+            origin.y + dx * sin + dy * cos,
+        )
+    }
+
+    pub(crate) fn distance(self, other: Vec2) -> f64 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+
+    pub(crate) fn lerp(self, other: Vec2, t: f64) -> Self {
+        Vec2::new(
+            self.x + (other.x - self.x) * t,
+            self.y + (other.y - self.y) * t,
+        )
+    }
+}
+
+impl Rect {
+    pub(crate) fn new(x: f64, y: f64, w: f64, h: f64) -> Self {
+        Rect { x, y, w, h }
+    }
+
+    pub(crate) fn center(self) -> Vec2 {
+        Vec2::new(self.x + self.w / 2.0, self.y + self.h / 2.0)
+    }
+
+    pub(crate) fn contains(self, point: Vec2) -> bool {
+        point.x >= self.x
+            && point.x <= self.x + self.w
+            && point.y >= self.y
+            && point.y <= self.y + self.h
+    }
+
+    pub(crate) fn overlaps(self, other: Rect) -> bool {
+        self.x < other.x + other.w
+            && other.x < self.x + self.w
+            && self.y < other.y + other.h
+            && other.y < self.y + self.h
+    }
+
+    pub(crate) fn intersection(self, other: Rect) -> Option<Rect> {
+        if !self.overlaps(other) {
+            return None;
+        }
+
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.w).min(other.x + other.w);
+        let bottom = (self.y + self.h).min(other.y + other.h);
+
+        Some(Rect::new(x, y, right - x, bottom - y))
+    }
+}
+
+// Accepts a Vec2, or any table with numeric `x`/`y` fields such as a Balatro `T` or `VT`.
+pub(crate) fn to_vec2(value: &LuaValue) -> LuaResult<Vec2> {
+    match value {
+        LuaValue::UserData(ud) => Ok(*ud.borrow::<Vec2>()?),
+        LuaValue::Table(tbl) => Ok(Vec2::new(
+            tbl.get::<Option<f64>>("x")?.unwrap_or(0.0),
+            tbl.get::<Option<f64>>("y")?.unwrap_or(0.0),
+        )),
+        _ => Err(mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: expected a Vec2, got {}",
+            value.type_name()
+        ))),
+    }
+}
+
+pub(crate) fn to_rect(value: &LuaValue) -> LuaResult<Rect> {
+    match value {
+        LuaValue::UserData(ud) => Ok(*ud.borrow::<Rect>()?),
+        LuaValue::Table(tbl) => Ok(Rect::new(
+            tbl.get::<Option<f64>>("x")?.unwrap_or(0.0),
+            tbl.get::<Option<f64>>("y")?.unwrap_or(0.0),
+            tbl.get::<Option<f64>>("w")?.unwrap_or(0.0),
+            tbl.get::<Option<f64>>("h")?.unwrap_or(0.0),
+        )),
+        _ => Err(mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: expected a Rect, got {}",
+            value.type_name()
+        ))),
+    }
+}
+
+fn vec2_operands(lhs: &LuaValue, rhs: &LuaValue) -> LuaResult<(Vec2, Vec2)> {
+    let as_operand = |value: &LuaValue| match value {
+        LuaValue::Number(n) => Ok(Vec2::new(*n, *n)),
+        LuaValue::Integer(n) => Ok(Vec2::new(*n as f64, *n as f64)),
+        other => to_vec2(other),
+    };
+
+    Ok((as_operand(lhs)?, as_operand(rhs)?))
+}
+
+fn write_transform(
+    lua: &Lua,
+    target: Option<LuaTable>,
+    fields: &[(&str, f64)],
+) -> LuaResult<LuaTable> {
+    let target = match target {
+        Some(tbl) => tbl,
+        None => lua.create_table()?,
+    };
+
+    for (key, val) in fields {
+        target.set(*key, *val)?;
+    }
+
+    Ok(target)
+}
+
+impl LuaUserData for Vec2 {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("x", |_, this| Ok(this.x));
+        fields.add_field_method_get("y", |_, this| Ok(this.y));
+        fields.add_field_method_set("x", |_, this, x: f64| {
+            this.x = x;
+            Ok(())
+        });
+        fields.add_field_method_set("y", |_, this, y: f64| {
+            this.y = y;
+            Ok(())
+        });
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("length", |_, this, ()| Ok(this.length()));
+        methods.add_method("normalize", |_, this, ()| Ok(this.normalize()));
+        methods.add_method("dot", |_, this, other: LuaValue| {
+            Ok(this.dot(to_vec2(&other)?))
+        });
+        methods.add_method("distance", |_, this, other: LuaValue| {
+            Ok(this.distance(to_vec2(&other)?))
+        });
+        methods.add_method(
+            "rotate",
+            |_, this, (angle, origin): (f64, Option<LuaValue>)| {
+                let origin = match origin {
+                    Some(origin) => to_vec2(&origin)?,
+                    None => Vec2::new(0.0, 0.0),
+                };
+
+                Ok(this.rotate(angle, origin))
+            },
+        );
+        methods.add_method("lerp", |_, this, (other, t): (LuaValue, f64)| {
+            Ok(this.lerp(to_vec2(&other)?, t))
+        });
+        methods.add_method("unpack", |_, this, ()| Ok((this.x, this.y)));
+        methods.add_method("to_transform", |lua, this, target: Option<LuaTable>| {
+            write_transform(lua, target, &[("x", this.x), ("y", this.y)])
+        });
+
+        methods.add_meta_function(LuaMetaMethod::Add, |_, (a, b): (LuaValue, LuaValue)| {
+            let (a, b) = vec2_operands(&a, &b)?;
+            Ok(Vec2::new(a.x + b.x, a.y + b.y))
+        });
+        methods.add_meta_function(LuaMetaMethod::Sub, |_, (a, b): (LuaValue, LuaValue)| {
+            let (a, b) = vec2_operands(&a, &b)?;
+            Ok(Vec2::new(a.x - b.x, a.y - b.y))
+        });
+        methods.add_meta_function(LuaMetaMethod::Mul, |_, (a, b): (LuaValue, LuaValue)| {
+            let (a, b) = vec2_operands(&a, &b)?;
+            Ok(Vec2::new(a.x * b.x, a.y * b.y))
+        });
+        methods.add_meta_function(LuaMetaMethod::Div, |_, (a, b): (LuaValue, LuaValue)| {
+            let (a, b) = vec2_operands(&a, &b)?;
+            Ok(Vec2::new(a.x / b.x, a.y / b.y))
+        });
+        methods.add_meta_method(LuaMetaMethod::Unm, |_, this, ()| {
+            Ok(Vec2::new(-this.x, -this.y))
+        });
+        methods.add_meta_method(LuaMetaMethod::Eq, |_, this, other: LuaAnyUserData| {
+            Ok(other.borrow::<Vec2>().is_ok_and(|other| *this == *other))
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!("Vec2({}, {})", this.x, this.y))
+        });
+    }
+}
+
+impl LuaUserData for Rect {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("x", |_, this| Ok(this.x));
+        fields.add_field_method_get("y", |_, this| Ok(this.y));
+        fields.add_field_method_get("w", |_, this| Ok(this.w));
+        fields.add_field_method_get("h", |_, this| Ok(this.h));
+        fields.add_field_method_set("x", |_, this, x: f64| {
+            this.x = x;
+            Ok(())
+        });
+        fields.add_field_method_set("y", |_, this, y: f64| {
+            this.y = y;
+            Ok(())
+        });
+        fields.add_field_method_set("w", |_, this, w: f64| {
+            this.w = w;
+            Ok(())
+        });
+        fields.add_field_method_set("h", |_, this, h: f64| {
+            this.h = h;
+            Ok(())
+        });
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("center", |_, this, ()| Ok(this.center()));
+        methods.add_method("position", |_, this, ()| Ok(Vec2::new(this.x, this.y)));
+        methods.add_method("size", |_, this, ()| Ok(Vec2::new(this.w, this.h)));
+        methods.add_method(
+            "contains",
+            |_, this, (point, y): (LuaValue, Option<f64>)| {
+                let point = match (point, y) {
+                    (LuaValue::Number(x), Some(y)) => Vec2::new(x, y),
+                    (LuaValue::Integer(x), Some(y)) => Vec2::new(x as f64, y),
+                    (point, _) => to_vec2(&point)?,
+                };
+
+                Ok(this.contains(point))
+            },
+        );
+        methods.add_method("overlaps", |_, this, other: LuaValue| {
+            Ok(this.overlaps(to_rect(&other)?))
+        });
+        methods.add_method("intersection", |_, this, other: LuaValue| {
+            Ok(this.intersection(to_rect(&other)?))
+        });
+        methods.add_method("translate", |_, this, offset: LuaValue| {
+            let offset = to_vec2(&offset)?;
+            Ok(Rect::new(
+                this.x + offset.x,
+                this.y + offset.y,
+                this.w,
+                this.h,
+            ))
+        });
+        methods.add_method("unpack", |_, this, ()| Ok((this.x, this.y, this.w, this.h)));
+        methods.add_method("to_transform", |lua, this, target: Option<LuaTable>| {
+            write_transform(
+                lua,
+                target,
+                &[("x", this.x), ("y", this.y), ("w", this.w), ("h", this.h)],
+            )
+        });
+
+        methods.add_meta_method(LuaMetaMethod::Eq, |_, this, other: LuaAnyUserData| {
+            Ok(other.borrow::<Rect>().is_ok_and(|other| *this == *other))
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!(
+                "Rect({}, {}, {}, {})",
+                this.x, this.y, this.w, this.h
+            ))
+        });
+    }
+}
+
+pub(crate) fn vec2(_: &Lua, (x, y): (Option<f64>, Option<f64>)) -> LuaResult<Vec2> {
+    Ok(Vec2::new(x.unwrap_or(0.0), y.unwrap_or(0.0)))
+}
+
+pub(crate) fn rect(
+    _: &Lua,
+    (x, y, w, h): (Option<f64>, Option<f64>, Option<f64>, Option<f64>),
+) -> LuaResult<Rect> {
+    Ok(Rect::new(
+        x.unwrap_or(0.0),
+        y.unwrap_or(0.0),
+        w.unwrap_or(0.0),
+        h.unwrap_or(0.0),
+    ))
+}
+
+pub(crate) fn vec2_from_transform(_: &Lua, transform: LuaValue) -> LuaResult<Vec2> {
+    to_vec2(&transform)
+}
+
+pub(crate) fn rect_from_transform(_: &Lua, transform: LuaValue) -> LuaResult<Rect> {
+    to_rect(&transform)
+}
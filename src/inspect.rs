@@ -0,0 +1,301 @@
+use crate::serial::{Format, format_number, is_identifier, lua_string};
+use crate::tbl::{compare_values, ordered_entries, seq_index, stable_sort_by};
+use mlua::prelude::*;
+use std::collections::HashSet;
+use std::ffi::c_void;
+
+const RESET: &str = "\x1b[0m";
+const KEY: &str = "\x1b[36m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[33m";
+const LITERAL: &str = "\x1b[35m";
+const MARKER: &str = "\x1b[2m";
+
+struct InspectOpts {
+    depth: usize,
+    max_items: usize,
+    max_string: usize,
+    indent: usize,
+    sort_keys: bool,
+    metatables: bool,
+    color: bool,
+    exclude: HashSet<String>,
+    filter: Option<LuaFunction>,
+}
+
+impl InspectOpts {
+    fn from_lua(opts: Option<&LuaTable>) -> LuaResult<Self> {
+        let mut inspect_opts = InspectOpts {
+            depth: 4,
+            max_items: 20,
+            max_string: 80,
+            indent: 2,
+            sort_keys: true,
+            metatables: false,
+            color: false,
+            exclude: HashSet::new(),
+            filter: None,
+        };
+
+        let Some(opts) = opts else {
+            return Ok(inspect_opts);
+        };
+
+        let count = |name: &str, default: usize| -> LuaResult<usize> {
+            Ok(opts
+                .get::<Option<f64>>(name)?
+                .map_or(default, |n| n.max(0.0) as usize))
+        };
+
+        inspect_opts.depth = count("depth", inspect_opts.depth)?;
+        inspect_opts.max_items = count("max_items", inspect_opts.max_items)?;
+        inspect_opts.max_string = count("max_string", inspect_opts.max_string)?;
+        inspect_opts.indent = count("indent", inspect_opts.indent)?;
+        inspect_opts.sort_keys = opts.get::<Option<bool>>("sort_keys")?.unwrap_or(true);
+        inspect_opts.metatables = opts.get::<Option<bool>>("metatables")?.unwrap_or(false);
+        inspect_opts.color = opts.get::<Option<bool>>("color")?.unwrap_or(false);
+        inspect_opts.filter = opts.get::<Option<LuaFunction>>("filter")?;
+
+        if let Some(exclude) = opts.get::<Option<LuaTable>>("exclude")? {
+            for key in exclude.sequence_values::<String>() {
+                inspect_opts.exclude.insert(key?);
+            }
+        }
+
+        Ok(inspect_opts)
+    }
+}
+
+fn key_label(key: &LuaValue) -> String {
+    key.to_string()
+        .unwrap_or_else(|_| format!("<{}>", key.type_name()))
+}
+
+struct Inspector {
+    opts: InspectOpts,
+    out: String,
+    stack: Vec<(*const c_void, String)>,
+    path: Vec<String>,
+}
+
+impl Inspector {
+    fn paint(&mut self, color: &str, text: &str) {
+        if self.opts.color {
+            self.out.push_str(color);
+            self.out.push_str(text);
+            self.out.push_str(RESET);
+        } else {
+            self.out.push_str(text);
+        }
+    }
+
+    fn newline(&mut self, depth: usize) {
+        if self.opts.indent > 0 {
+            self.out.push('\n');
+            self.out.push_str(&" ".repeat(self.opts.indent * depth));
+        } else {
+            self.out.push(' ');
+        }
+    }
+
+    fn path_text(&self) -> String {
+        if self.path.is_empty() {
+            "<root>".to_string()
+        } else {
+            self.path.join(".")
+        }
+    }
+
+    fn write_string(&mut self, s: &LuaString) {
+        let bytes = s.as_bytes();
+        let text = String::from_utf8_lossy(&bytes);
+        let char_count = text.chars().count();
+
+        let mut quoted = String::new();
+        if char_count > self.opts.max_string {
+            let cut: String = text.chars().take(self.opts.max_string).collect();
+            lua_string(cut.as_bytes(), &mut quoted);
+            quoted.push_str(&format!("...({} chars)", char_count));
+        } else {
+            lua_string(&bytes, &mut quoted);
+        }
+
+        self.paint(STRING, &quoted);
+    }
+
+    fn write_value(&mut self, value: &LuaValue, depth: usize) -> LuaResult<()> {
+        match value {
+            LuaValue::Nil => self.paint(LITERAL, "nil"),
+            LuaValue::Boolean(b) => self.paint(LITERAL, if *b { "true" } else { "false" }),
+            LuaValue::Integer(n) => self.paint(NUMBER, &n.to_string()),
+            LuaValue::Number(n) => self.paint(NUMBER, &format_number(*n, Format::Lua)),
+            LuaValue::String(s) => self.write_string(s),
+            LuaValue::Table(tbl) => self.write_table(tbl, depth)?,
+            other => self.paint(MARKER, &format!("<{}>", other.type_name())),
+        }
+
+        Ok(())
+    }
+
+    fn write_key(&mut self, key: &LuaValue) -> LuaResult<()> {
+        match key {
+            LuaValue::String(s) if is_identifier(&s.to_string_lossy()) => {
+                self.paint(KEY, &s.to_string_lossy());
+            }
+            _ => {
+                self.out.push('[');
+                self.write_value(key, usize::MAX)?;
+                self.out.push(']');
+            }
+        }
+        self.out.push_str(" = ");
+
+        Ok(())
+    }
+
+    fn is_shown(&self, key: &LuaValue, value: &LuaValue) -> LuaResult<bool> {
+        if let LuaValue::String(s) = key
+            && self.opts.exclude.contains(&*s.to_string_lossy())
+        {
+            return Ok(false);
+        }
+
+        match &self.opts.filter {
+            Some(filter) => filter.call((key.clone(), value.clone(), self.path_text())),
+            None => Ok(true),
+        }
+    }
+
+    fn write_table(&mut self, tbl: &LuaTable, depth: usize) -> LuaResult<()> {
+        if let Some((_, at)) = self.stack.iter().find(|(ptr, _)| *ptr == tbl.to_pointer()) {
+            let marker = format!("<cycle: {}>", at);
+            self.paint(MARKER, &marker);
+            return Ok(());
+        }
+
+        let mut entries = Vec::new();
+        let mut array_len = 0;
+        for (k, v, in_array) in ordered_entries(tbl)? {
+            self.path.push(key_label(&k));
+            let shown = self.is_shown(&k, &v)?;
+            self.path.pop();
+
+            // Array entries stay positional only while none before them were filtered out.
+            if shown {
+                let positional = in_array && seq_index(&k) == Some(array_len + 1);
+                if positional {
+                    array_len += 1;
+                }
+                entries.push((k, v, positional));
+            }
+        }
+
+        let metatable = if self.opts.metatables {
+            tbl.metatable()
+        } else {
+            None
+        };
+
+        if entries.is_empty() && metatable.is_none() {
+            self.out.push_str("{}");
+            return Ok(());
+        }
+
+        if depth >= self.opts.depth {
+            let marker = match entries.len() {
+                1 => "{...1 item}".to_string(),
+                n => format!("{{...{} items}}", n),
+            };
+            self.paint(MARKER, &marker);
+            return Ok(());
+        }
+
+        if self.opts.sort_keys {
+            let hash = entries.split_off(array_len);
+            entries.extend(stable_sort_by(hash, |(a, _, _), (b, _, _)| {
+                compare_values(a, b)
+            }));
+        }
+
+        self.stack.push((tbl.to_pointer(), self.path_text()));
+        self.out.push('{');
+
+        let total = entries.len();
+        let mut written = 0;
+        for (k, v, positional) in entries.into_iter().take(self.opts.max_items) {
+            if written > 0 {
+                self.out.push(',');
+            }
+            self.newline(depth + 1);
+
+            self.path.push(key_label(&k));
+            if !positional {
+                self.write_key(&k)?;
+            }
+            self.write_value(&v, depth + 1)?;
+            self.path.pop();
+
+            written += 1;
+        }
+
+        if total > written {
+            if written > 0 {
+                self.out.push(',');
+            }
+            self.newline(depth + 1);
+            let marker = format!("...{} more", total - written);
+            self.paint(MARKER, &marker);
+            written += 1;
+        }
+
+        if let Some(mt) = metatable {
+            if written > 0 {
+                self.out.push(',');
+            }
+            self.newline(depth + 1);
+            self.paint(MARKER, "<metatable>");
+            self.out.push_str(" = ");
+            self.path.push("<metatable>".to_string());
+            self.write_table(&mt, depth + 1)?;
+            self.path.pop();
+        }
+
+        self.newline(depth);
+        self.out.push('}');
+        self.stack.pop();
+
+        Ok(())
+    }
+}
+
+pub(crate) fn inspect(_: &Lua, (value, opts): (LuaValue, Option<LuaTable>)) -> LuaResult<String> {
+    let mut inspector = Inspector {
+        opts: InspectOpts::from_lua(opts.as_ref())?,
+        out: String::new(),
+        stack: Vec::new(),
+        path: Vec::new(),
+    };
+
+    inspector.write_value(&value, 0)?;
+
+    Ok(inspector.out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holes_are_not_printed_as_nil() {
+        let lua = Lua::new();
+        let value: LuaValue = lua
+            .load("return {[1] = 1, [2] = 2, [4] = 4}")
+            .eval()
+            .unwrap();
+        let opts = lua.create_table().unwrap();
+        opts.set("indent", 0).unwrap();
+
+        let text = inspect(&lua, (value, Some(opts))).unwrap();
+        assert_eq!(text, "{ 1, 2, [4] = 4 }");
+    }
+}
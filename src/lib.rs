@@ -1,13 +1,43 @@
 #![allow(clippy::only_used_in_recursion)]
 
+// Unit tests run outside the game and need a LuaJIT of their own to link against.
+#[cfg(test)]
+extern crate lua_test_host;
+
+mod array;
+mod diff;
+mod geom;
+mod inspect;
+mod locale;
+mod markup;
 mod math;
+mod odds;
+mod poker;
+mod proxy;
 mod rng;
+mod schema;
+mod score;
+mod serial;
+mod set;
 mod tbl;
 mod text;
 mod utils;
 
+use array::*;
+use diff::*;
+use geom::*;
+use inspect::*;
+use locale::*;
+use markup::*;
 use math::*;
+use odds::*;
+use poker::*;
+use proxy::*;
 use rng::*;
+use schema::*;
+use score::*;
+use serial::*;
+use set::*;
 use tbl::*;
 use text::*;
 use utils::*;
@@ -45,6 +75,96 @@ mkmodule! {
         count_num_of_joker,
         register_items,
         register_items_adv,
-        animate_center
+        animate_center,
+        approx_eq,
+        inverse_lerp,
+        remap,
+        wrap,
+        snap,
+        vec2,
+        rect,
+        vec2_from_transform,
+        rect_from_transform,
+        stats,
+        compile_expr,
+        eval_expr,
+        evaluate_poker_hand,
+        register_poker_hand,
+        unregister_poker_hand,
+        hypergeometric,
+        suit_draw_odds,
+        rank_draw_odds,
+        hand_draw_odds,
+        deck_composition,
+        simulate_score,
+        deep_copy,
+        deep_merge,
+        deep_equal,
+        map,
+        filter,
+        reduce,
+        find,
+        any,
+        all,
+        group_by,
+        partition,
+        zip,
+        flat_map,
+        count_by,
+        index_by,
+        sort_by,
+        sort_by_in_place,
+        to_json,
+        from_json,
+        to_lua_literal,
+        from_lua_literal,
+        get_path,
+        set_path,
+        has_path,
+        query,
+        validate,
+        assert_schema,
+        diff,
+        patch,
+        format_diff,
+        inspect,
+        scale_values,
+        smallest_val,
+        argmax_by,
+        argmin_by,
+        top_k,
+        rank,
+        slice,
+        chunk,
+        flatten,
+        unique,
+        rotate,
+        sliding_window,
+        interleave,
+        insert_at,
+        remove_where,
+        set,
+        multiset,
+        freeze,
+        observe,
+        proxy_target,
+        proxy_pairs,
+        proxy_ipairs,
+        title_case,
+        sentence_case,
+        snake_case,
+        kebab_case,
+        camel_case,
+        parse_markup,
+        lint_markup,
+        lint_localization,
+        load_locales,
+        compare_locales,
+        locale_stubs,
+        wrap_description,
+        edit_distance,
+        fuzzy_match,
+        fuzzy_search,
+        search_centers
     ]
 }
@@ -0,0 +1,454 @@
+use crate::markup::markup_vars;
+use crate::serial::parse_lua_literal;
+use crate::tbl::{compare_values, is_sequence, stable_sort_by};
+use mlua::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+// Every translatable leaf keyed by its dotted path: a string, or a list of description lines.
+type LocaleEntries = BTreeMap<String, Vec<String>>;
+
+#[derive(Default)]
+struct LocaleReport {
+    missing: Vec<String>,
+    extra: Vec<String>,
+    mismatched: Vec<(String, BTreeSet<usize>, BTreeSet<usize>)>,
+    translated: usize,
+    total: usize,
+}
+
+fn key_text(key: &LuaValue) -> String {
+    match key {
+        LuaValue::String(s) => s.to_string_lossy(),
+        other => other.to_string().unwrap_or_default(),
+    }
+}
+
+fn join_path(prefix: &str, key: &LuaValue) -> String {
+    if prefix.is_empty() {
+        key_text(key)
+    } else {
+        format!("{}.{}", prefix, key_text(key))
+    }
+}
+
+fn is_leaf_list(tbl: &LuaTable) -> LuaResult<bool> {
+    if tbl.raw_len() == 0 || !is_sequence(tbl)? {
+        return Ok(false);
+    }
+
+    for value in tbl.sequence_values::<LuaValue>() {
+        if !matches!(value?, LuaValue::String(_)) {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+fn sorted_pairs(tbl: &LuaTable) -> LuaResult<Vec<(LuaValue, LuaValue)>> {
+    let pairs = tbl
+        .pairs::<LuaValue, LuaValue>()
+        .collect::<LuaResult<Vec<_>>>()?;
+
+    Ok(stable_sort_by(pairs, |(a, _), (b, _)| compare_values(a, b)))
+}
+
+fn collect_entries(tbl: &LuaTable, prefix: &str, out: &mut LocaleEntries) -> LuaResult<()> {
+    for (key, value) in sorted_pairs(tbl)? {
+        let path = join_path(prefix, &key);
+
+        match value {
+            LuaValue::Table(child) if is_leaf_list(&child)? => {
+                let lines = child
+                    .sequence_values::<LuaString>()
+                    .map(|line| line.map(|line| line.to_string_lossy()))
+                    .collect::<LuaResult<_>>()?;
+                out.insert(path, lines);
+            }
+            LuaValue::Table(child) => collect_entries(&child, &path, out)?,
+            LuaValue::String(s) => {
+                out.insert(path, vec![s.to_string_lossy()]);
+            }
+            other => {
+                out.insert(path, vec![other.to_string().unwrap_or_default()]);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn vars_of(lines: &[String]) -> BTreeSet<usize> {
+    lines.iter().flat_map(|line| markup_vars(line)).collect()
+}
+
+fn compare(base: &LocaleEntries, other: &LocaleEntries) -> LocaleReport {
+    let mut report = LocaleReport {
+        total: base.len(),
+        ..LocaleReport::default()
+    };
+
+    for (path, lines) in base {
+        let Some(translated) = other.get(path) else {
+            report.missing.push(path.clone());
+            continue;
+        };
+
+        report.translated += 1;
+        let (expected, found) = (vars_of(lines), vars_of(translated));
+        if expected != found {
+            report.mismatched.push((path.clone(), expected, found));
+        }
+    }
+
+    report.extra = other
+        .keys()
+        .filter(|path| !base.contains_key(*path))
+        .cloned()
+        .collect();
+
+    report
+}
+
+fn summary_line(name: &str, report: &LocaleReport) -> String {
+    let coverage = if report.total == 0 {
+        100.0
+    } else {
+        report.translated as f64 / report.total as f64 * 100.0
+    };
+
+    format!(
+        "{}: {:.1}% ({}/{}), {} missing, {} extra, {} variable mismatches",
+        name,
+        coverage,
+        report.translated,
+        report.total,
+        report.missing.len(),
+        report.extra.len(),
+        report.mismatched.len()
+    )
+}
+
+fn vars_list(lua: &Lua, vars: &BTreeSet<usize>) -> LuaResult<LuaTable> {
+    lua.create_sequence_from(vars.iter().copied())
+}
+
+fn report_to_lua(lua: &Lua, report: &LocaleReport) -> LuaResult<LuaTable> {
+    let result = lua.create_table()?;
+    result.set("missing", lua.create_sequence_from(report.missing.clone())?)?;
+    result.set("extra", lua.create_sequence_from(report.extra.clone())?)?;
+
+    let mismatched = lua.create_table()?;
+    for (path, expected, found) in &report.mismatched {
+        let entry = lua.create_table()?;
+        entry.set("path", path.as_str())?;
+        entry.set("expected", vars_list(lua, expected)?)?;
+        entry.set("found", vars_list(lua, found)?)?;
+        mismatched.raw_push(entry)?;
+    }
+    result.set("mismatched", mismatched)?;
+
+    result.set("translated", report.translated)?;
+    result.set("total", report.total)?;
+    result.set(
+        "coverage",
+        if report.total == 0 {
+            1.0
+        } else {
+            report.translated as f64 / report.total as f64
+        },
+    )?;
+
+    Ok(result)
+}
+
+fn load_file(lua: &Lua, path: &Path) -> LuaResult<LuaValue> {
+    let bytes = std::fs::read(path).map_err(|err| {
+        mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: cannot read {}: {}",
+            path.display(),
+            err
+        ))
+    })?;
+
+    parse_lua_literal(lua, &bytes).map_err(|err| {
+        mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: cannot parse {}: {}",
+            path.display(),
+            err
+        ))
+    })
+}
+
+// Accepts a directory of `<locale>.lua` files, a list of file paths, or a name -> path map.
+// Files are parsed as data, never executed.
+pub(crate) fn load_locales(lua: &Lua, source: LuaValue) -> LuaResult<LuaTable> {
+    let mut files: Vec<(String, std::path::PathBuf)> = Vec::new();
+    let stem = |path: &Path| {
+        path.file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+
+    match source {
+        LuaValue::String(dir) => {
+            let dir = dir.to_str()?.to_string();
+            let entries = std::fs::read_dir(&dir).map_err(|err| {
+                mlua::Error::RuntimeError(format!(
+                    "[INSOLENCE] Error: cannot read {}: {}",
+                    dir, err
+                ))
+            })?;
+
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|ext| ext == "lua") {
+                    files.push((stem(&path), path));
+                }
+            }
+        }
+        LuaValue::Table(tbl) => {
+            for (key, value) in sorted_pairs(&tbl)? {
+                let path = std::path::PathBuf::from(String::from_lua(value, lua)?);
+                let name = match key {
+                    LuaValue::String(name) => name.to_str()?.to_string(),
+                    _ => stem(&path),
+                };
+                files.push((name, path));
+            }
+        }
+        other => {
+            return Err(mlua::Error::RuntimeError(format!(
+                "[INSOLENCE] Error: expected a directory or list of locale files, got {}",
+                other.type_name()
+            )));
+        }
+    }
+
+    let locales = lua.create_table()?;
+    for (name, path) in files {
+        locales.set(name, load_file(lua, &path)?)?;
+    }
+
+    Ok(locales)
+}
+
+pub(crate) fn compare_locales(
+    lua: &Lua,
+    (locales, opts): (LuaTable, Option<LuaTable>),
+) -> LuaResult<(LuaTable, String)> {
+    let base_name = match &opts {
+        Some(opts) => opts.get::<Option<String>>("base")?,
+        None => None,
+    }
+    .unwrap_or_else(|| "en-us".to_string());
+
+    let Some(base_tbl) = locales.get::<Option<LuaTable>>(base_name.as_str())? else {
+        return Err(mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: base locale {} is not loaded",
+            base_name
+        )));
+    };
+
+    let mut base = LocaleEntries::new();
+    collect_entries(&base_tbl, "", &mut base)?;
+
+    let result = lua.create_table()?;
+    let mut summary = Vec::new();
+
+    for (name, tbl) in sorted_pairs(&locales)? {
+        let name = key_text(&name);
+        let LuaValue::Table(tbl) = tbl else {
+            continue;
+        };
+        if name == base_name {
+            continue;
+        }
+
+        let mut entries = LocaleEntries::new();
+        collect_entries(&tbl, "", &mut entries)?;
+
+        let report = compare(&base, &entries);
+        summary.push(summary_line(&name, &report));
+        result.set(name, report_to_lua(lua, &report)?)?;
+    }
+
+    Ok((result, summary.join("\n")))
+}
+
+fn mark_stub(lua: &Lua, value: LuaValue, marker: &str) -> LuaResult<LuaValue> {
+    match value {
+        LuaValue::String(s) => Ok(LuaValue::String(lua.create_string(format!(
+            "{}{}",
+            marker,
+            s.to_string_lossy()
+        ))?)),
+        LuaValue::Table(lines) if is_leaf_list(&lines)? => {
+            let marked = lines
+                .sequence_values::<LuaValue>()
+                .map(|line| mark_stub(lua, line?, marker))
+                .collect::<LuaResult<Vec<_>>>()?;
+            Ok(LuaValue::Table(lua.create_sequence_from(marked)?))
+        }
+        LuaValue::Table(tbl) => {
+            let copy = lua.create_table()?;
+            for (key, value) in sorted_pairs(&tbl)? {
+                copy.raw_set(key, mark_stub(lua, value, marker)?)?;
+            }
+            Ok(LuaValue::Table(copy))
+        }
+        other => Ok(other),
+    }
+}
+
+// Mirrors the base structure but keeps only what `target` lacks; None when nothing is missing.
+fn stubs_for(
+    lua: &Lua,
+    base: &LuaTable,
+    target: Option<&LuaTable>,
+    marker: &str,
+) -> LuaResult<Option<LuaTable>> {
+    let stubs = lua.create_table()?;
+    let mut any = false;
+
+    for (key, value) in sorted_pairs(base)? {
+        let existing = match target {
+            Some(target) => target.raw_get::<LuaValue>(key.clone())?,
+            None => LuaValue::Nil,
+        };
+
+        let stub = match (&value, &existing) {
+            (LuaValue::Table(child), LuaValue::Table(other)) if !is_leaf_list(child)? => {
+                stubs_for(lua, child, Some(other), marker)?.map(LuaValue::Table)
+            }
+            (_, LuaValue::Nil) => Some(mark_stub(lua, value.clone(), marker)?),
+            _ => None,
+        };
+
+        if let Some(stub) = stub {
+            stubs.raw_set(key, stub)?;
+            any = true;
+        }
+    }
+
+    Ok(if any { Some(stubs) } else { None })
+}
+
+pub(crate) fn locale_stubs(
+    lua: &Lua,
+    (base, target, opts): (LuaTable, Option<LuaTable>, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let marker = match &opts {
+        Some(opts) => opts.get::<Option<String>>("marker")?,
+        None => None,
+    }
+    .unwrap_or_else(|| "[TODO] ".to_string());
+
+    match stubs_for(lua, &base, target.as_ref(), &marker)? {
+        Some(stubs) => Ok(stubs),
+        None => lua.create_table(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(lua: &Lua, source: &str) -> LuaTable {
+        lua.load(source).eval().unwrap()
+    }
+
+    fn entries(tbl: &LuaTable) -> LocaleEntries {
+        let mut out = LocaleEntries::new();
+        collect_entries(tbl, "", &mut out).unwrap();
+        out
+    }
+
+    const BASE: &str = r#"{
+        descriptions = {
+            Joker = {
+                j_a = { name = "A", text = { "{C:mult}+#1#{} Mult", "gains #2#" } },
+                j_b = { name = "B", text = { "plain" } },
+            },
+        },
+        misc = { dictionary = { k_ok = "OK", k_lvl = "Lvl #1#" } },
+    }"#;
+
+    const FRENCH: &str = r#"{
+        descriptions = {
+            Joker = {
+                j_a = { name = "A", text = { "{C:mult}+#1#{} Multi" } },
+            },
+        },
+        misc = { dictionary = { k_ok = "D'accord", k_extra = "en trop" } },
+    }"#;
+
+    #[test]
+    fn compare_reports_missing_extra_and_variable_mismatches() {
+        let lua = Lua::new();
+        let report = compare(&entries(&table(&lua, BASE)), &entries(&table(&lua, FRENCH)));
+
+        assert_eq!(
+            report.missing,
+            [
+                "descriptions.Joker.j_b.name",
+                "descriptions.Joker.j_b.text",
+                "misc.dictionary.k_lvl",
+            ]
+        );
+        assert_eq!(report.extra, ["misc.dictionary.k_extra"]);
+        assert_eq!(report.mismatched.len(), 1);
+
+        let (path, expected, found) = &report.mismatched[0];
+        assert_eq!(path, "descriptions.Joker.j_a.text");
+        assert_eq!(expected.iter().copied().collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(found.iter().copied().collect::<Vec<_>>(), [1]);
+        assert_eq!((report.translated, report.total), (3, 6));
+    }
+
+    #[test]
+    fn summary_line_shows_coverage() {
+        let lua = Lua::new();
+        let report = compare(&entries(&table(&lua, BASE)), &entries(&table(&lua, FRENCH)));
+
+        assert_eq!(
+            summary_line("fr", &report),
+            "fr: 50.0% (3/6), 3 missing, 1 extra, 1 variable mismatches"
+        );
+        assert_eq!(
+            summary_line("empty", &LocaleReport::default()),
+            "empty: 100.0% (0/0), 0 missing, 0 extra, 0 variable mismatches"
+        );
+    }
+
+    #[test]
+    fn stubs_only_cover_missing_leaves() {
+        let lua = Lua::new();
+        let base = table(&lua, BASE);
+        let french = table(&lua, FRENCH);
+
+        let stubs = stubs_for(&lua, &base, Some(&french), "[TODO] ")
+            .unwrap()
+            .unwrap();
+        let stubs = entries(&stubs);
+
+        let expected: LocaleEntries = [
+            ("descriptions.Joker.j_b.name", vec!["[TODO] B"]),
+            ("descriptions.Joker.j_b.text", vec!["[TODO] plain"]),
+            ("misc.dictionary.k_lvl", vec!["[TODO] Lvl #1#"]),
+        ]
+        .into_iter()
+        .map(|(path, lines)| {
+            (
+                path.to_string(),
+                lines.into_iter().map(String::from).collect(),
+            )
+        })
+        .collect();
+        assert_eq!(stubs, expected);
+
+        let complete = stubs_for(&lua, &base, Some(&base), "[TODO] ").unwrap();
+        assert!(complete.is_none());
+    }
+}
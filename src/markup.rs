@@ -0,0 +1,779 @@
+use crate::tbl::{lookup_path, parse_path};
+use mlua::prelude::*;
+use std::collections::HashSet;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Control keys understood by the game's description renderer.
+const MODIFIER_KEYS: [&str; 7] = ["C", "X", "V", "B", "E", "T", "s"];
+const COLOR_KEYS: [&str; 2] = ["C", "X"];
+// `{V:1}` and `{B:1}` index into the `colours` list returned by loc_vars.
+const INDEX_KEYS: [&str; 2] = ["V", "B"];
+
+type Style = Vec<(String, String)>;
+
+enum Node {
+    Text(String),
+    Var(usize),
+    Span(Style, Vec<Node>),
+}
+
+struct Issue {
+    column: usize,
+    message: String,
+}
+
+struct MarkupParser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    issues: Vec<Issue>,
+    vars: Vec<(usize, usize)>,
+    colors: Option<&'a HashSet<String>>,
+}
+
+impl<'a> MarkupParser<'a> {
+    fn new(line: &str, colors: Option<&'a HashSet<String>>) -> Self {
+        MarkupParser {
+            chars: line.chars().collect(),
+            pos: 0,
+            issues: Vec::new(),
+            vars: Vec::new(),
+            colors,
+        }
+    }
+
+    fn issue(&mut self, column: usize, message: String) {
+        self.issues.push(Issue {
+            column: column + 1,
+            message,
+        });
+    }
+
+    fn find(&self, from: usize, target: char) -> Option<usize> {
+        self.chars[from..]
+            .iter()
+            .position(|&c| c == target)
+            .map(|offset| from + offset)
+    }
+
+    fn parse_style(&mut self, start: usize, body: &str) -> Style {
+        let mut style = Vec::new();
+
+        for part in body.split(',') {
+            let Some((key, value)) = part.split_once(':') else {
+                self.issue(
+                    start,
+                    format!("malformed style \"{}\" (expected key:value)", part),
+                );
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+
+            if !MODIFIER_KEYS.contains(&key) {
+                self.issue(start, format!("unknown style key \"{}\"", key));
+            } else if INDEX_KEYS.contains(&key) && !value.parse::<usize>().is_ok_and(|idx| idx > 0)
+            {
+                self.issue(
+                    start,
+                    format!("{} needs a positive color index, got \"{}\"", key, value),
+                );
+            } else if COLOR_KEYS.contains(&key)
+                && let Some(colors) = self.colors
+                && !colors.contains(&value.to_lowercase())
+            {
+                self.issue(start, format!("unknown color \"{}\"", value));
+            }
+
+            style.push((key.to_string(), value.to_string()));
+        }
+
+        style
+    }
+
+    fn push_text(nodes: &mut Vec<Node>, text: &str) {
+        if let Some(Node::Text(last)) = nodes.last_mut() {
+            last.push_str(text);
+        } else if !text.is_empty() {
+            nodes.push(Node::Text(text.to_string()));
+        }
+    }
+
+    // `{}` closes the current span and `{K:v}` starts a new one; spans never nest.
+    fn parse(&mut self) -> Vec<Node> {
+        let mut top: Vec<Node> = Vec::new();
+        let mut span: Option<(Style, Vec<Node>)> = None;
+
+        while self.pos < self.chars.len() {
+            let ch = self.chars[self.pos];
+            let nodes = match &mut span {
+                Some((_, children)) => children,
+                None => &mut top,
+            };
+
+            match ch {
+                '{' => {
+                    let start = self.pos;
+                    let close = self.find(start + 1, '}');
+                    let reopen = self.find(start + 1, '{');
+
+                    let Some(close) = close.filter(|&close| reopen.is_none_or(|r| close < r))
+                    else {
+                        self.issue(start, "unclosed \"{\"".into());
+                        Self::push_text(nodes, "{");
+                        self.pos += 1;
+                        continue;
+                    };
+
+                    let body: String = self.chars[start + 1..close].iter().collect();
+                    if let Some((style, children)) = span.take() {
+                        top.push(Node::Span(style, children));
+                    }
+                    if !body.trim().is_empty() {
+                        span = Some((self.parse_style(start, &body), Vec::new()));
+                    }
+                    self.pos = close + 1;
+                }
+                '}' => {
+                    self.issue(self.pos, "unmatched \"}\"".into());
+                    Self::push_text(nodes, "}");
+                    self.pos += 1;
+                }
+                '#' => {
+                    let start = self.pos;
+                    let close = self.find(start + 1, '#');
+                    let body: Option<String> =
+                        close.map(|close| self.chars[start + 1..close].iter().collect());
+
+                    match (close, body.as_deref().map(str::parse::<usize>)) {
+                        (Some(close), Some(Ok(index))) if index > 0 => {
+                            nodes.push(Node::Var(index));
+                            self.vars.push((index, start));
+                            self.pos = close + 1;
+                        }
+                        (None, _) => {
+                            self.issue(start, "unterminated \"#\" variable".into());
+                            Self::push_text(nodes, "#");
+                            self.pos += 1;
+                        }
+                        (Some(close), _) => {
+                            let literal: String = self.chars[start..=close].iter().collect();
+                            self.issue(start, format!("invalid variable {}", literal));
+                            Self::push_text(nodes, &literal);
+                            self.pos = close + 1;
+                        }
+                    }
+                }
+                _ => {
+                    let end = self.chars[self.pos..]
+                        .iter()
+                        .position(|c| matches!(c, '{' | '}' | '#'))
+                        .map_or(self.chars.len(), |offset| self.pos + offset);
+                    let text: String = self.chars[self.pos..end].iter().collect();
+                    Self::push_text(nodes, &text);
+                    self.pos = end;
+                }
+            }
+        }
+
+        if let Some((style, children)) = span {
+            top.push(Node::Span(style, children));
+        }
+
+        top
+    }
+}
+
+// Indices of every well-formed `#n#` variable in a line, in order of appearance.
+pub(crate) fn markup_vars(line: &str) -> Vec<usize> {
+    let mut parser = MarkupParser::new(line, None);
+    parser.parse();
+
+    parser.vars.into_iter().map(|(index, _)| index).collect()
+}
+
+fn nodes_to_lua(lua: &Lua, nodes: Vec<Node>) -> LuaResult<LuaTable> {
+    let result = lua.create_table()?;
+
+    for node in nodes {
+        let entry = lua.create_table()?;
+        match node {
+            Node::Text(text) => {
+                entry.set("type", "text")?;
+                entry.set("text", text)?;
+            }
+            Node::Var(index) => {
+                entry.set("type", "var")?;
+                entry.set("index", index)?;
+            }
+            Node::Span(style, children) => {
+                let style_tbl = lua.create_table()?;
+                for (key, value) in style {
+                    style_tbl.set(key, value)?;
+                }
+                entry.set("type", "span")?;
+                entry.set("style", style_tbl)?;
+                entry.set("children", nodes_to_lua(lua, children)?)?;
+            }
+        }
+        result.raw_push(entry)?;
+    }
+
+    Ok(result)
+}
+
+fn markup_lines(value: LuaValue) -> LuaResult<Vec<String>> {
+    match value {
+        LuaValue::String(line) => Ok(vec![line.to_str()?.to_string()]),
+        LuaValue::Table(lines) => lines.sequence_values::<String>().collect(),
+        other => Err(mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: expected a line or list of lines, got {}",
+            other.type_name()
+        ))),
+    }
+}
+
+pub(crate) fn parse_markup(lua: &Lua, lines: LuaValue) -> LuaResult<LuaTable> {
+    let single = matches!(lines, LuaValue::String(_));
+    let mut parsed = Vec::new();
+
+    for line in markup_lines(lines)? {
+        parsed.push(nodes_to_lua(lua, MarkupParser::new(&line, None).parse())?);
+    }
+
+    match (single, parsed.pop()) {
+        (true, Some(nodes)) => Ok(nodes),
+        (_, last) => {
+            parsed.extend(last);
+            lua.create_sequence_from(parsed)
+        }
+    }
+}
+
+fn table_keys(tbl: &LuaTable, out: &mut HashSet<String>) -> LuaResult<()> {
+    for pair in tbl.pairs::<LuaValue, LuaValue>() {
+        if let (LuaValue::String(key), _) = pair? {
+            out.insert(key.to_str()?.to_lowercase());
+        }
+    }
+
+    Ok(())
+}
+
+// Known color names from `opts.colors`, or from G.C and G.ARGS.LOC_COLOURS when running in game.
+// None disables color checks.
+fn known_colors(lua: &Lua, opts: Option<&LuaTable>) -> LuaResult<Option<HashSet<String>>> {
+    let mut colors = HashSet::new();
+
+    if let Some(opts) = opts
+        && let Some(list) = opts.get::<Option<LuaTable>>("colors")?
+    {
+        for name in list.sequence_values::<String>() {
+            colors.insert(name?.to_lowercase());
+        }
+        return Ok(Some(colors));
+    }
+
+    let globals = LuaValue::Table(lua.globals());
+    for path in ["G.C", "G.ARGS.LOC_COLOURS"] {
+        if let LuaValue::Table(tbl) = lookup_path(globals.clone(), &parse_path(path))? {
+            table_keys(&tbl, &mut colors)?;
+        }
+    }
+
+    Ok(if colors.is_empty() {
+        None
+    } else {
+        Some(colors)
+    })
+}
+
+fn var_count(value: LuaValue) -> Option<usize> {
+    match value {
+        LuaValue::Integer(n) => Some(n.max(0) as usize),
+        LuaValue::Number(n) => Some(n.max(0.0) as usize),
+        LuaValue::Table(vars) => Some(vars.raw_len()),
+        _ => None,
+    }
+}
+
+struct Linter {
+    colors: Option<HashSet<String>>,
+    issues: Vec<(String, usize, usize, String)>,
+}
+
+impl Linter {
+    fn lint_lines(&mut self, path: &str, lines: &[String], vars: Option<usize>) {
+        let mut used = HashSet::new();
+
+        for (line_no, line) in lines.iter().enumerate() {
+            let mut parser = MarkupParser::new(line, self.colors.as_ref());
+            parser.parse();
+
+            for issue in parser.issues {
+                self.issues
+                    .push((path.to_string(), line_no + 1, issue.column, issue.message));
+            }
+
+            for (index, column) in parser.vars {
+                used.insert(index);
+                if let Some(count) = vars
+                    && index > count
+                {
+                    self.issues.push((
+                        path.to_string(),
+                        line_no + 1,
+                        column + 1,
+                        format!(
+                            "variable #{}# has no matching entry in loc_vars ({} given)",
+                            index, count
+                        ),
+                    ));
+                }
+            }
+        }
+
+        if let Some(count) = vars {
+            for index in (1..=count).filter(|index| !used.contains(index)) {
+                self.issues.push((
+                    path.to_string(),
+                    0,
+                    0,
+                    format!("loc_vars entry {} is never used", index),
+                ));
+            }
+        }
+    }
+
+    fn to_lua(&self, lua: &Lua) -> LuaResult<LuaTable> {
+        let result = lua.create_table()?;
+
+        for (path, line, column, message) in &self.issues {
+            let entry = lua.create_table()?;
+            if !path.is_empty() {
+                entry.set("path", path.as_str())?;
+            }
+            if *line > 0 {
+                entry.set("line", *line)?;
+                entry.set("column", *column)?;
+            }
+            entry.set("message", message.as_str())?;
+            result.raw_push(entry)?;
+        }
+
+        Ok(result)
+    }
+}
+
+pub(crate) fn lint_markup(
+    lua: &Lua,
+    (lines, opts): (LuaValue, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let vars = match &opts {
+        Some(opts) => var_count(opts.get("loc_vars")?),
+        None => None,
+    };
+    let mut linter = Linter {
+        colors: known_colors(lua, opts.as_ref())?,
+        issues: Vec::new(),
+    };
+
+    linter.lint_lines("", &markup_lines(lines)?, vars);
+    linter.to_lua(lua)
+}
+
+// Walks a localization table (or just its `descriptions`) and lints every `text` and `name`.
+// `opts.loc_vars` may map center keys to a loc_vars count or array.
+fn walk_localization(
+    linter: &mut Linter,
+    tbl: &LuaTable,
+    path: &mut Vec<String>,
+    loc_vars: Option<&LuaTable>,
+    seen: &mut HashSet<*const std::ffi::c_void>,
+) -> LuaResult<()> {
+    if !seen.insert(tbl.to_pointer()) {
+        return Ok(());
+    }
+
+    let key = path.last().cloned().unwrap_or_default();
+    let vars = match loc_vars {
+        Some(loc_vars) => var_count(loc_vars.get(key.as_str())?),
+        None => None,
+    };
+
+    let joined = path.join(".");
+    if let LuaValue::Table(text) = tbl.raw_get::<LuaValue>("text")? {
+        let lines: Vec<String> = text
+            .sequence_values::<LuaValue>()
+            .filter_map(|line| match line {
+                Ok(LuaValue::String(s)) => Some(Ok(s.to_string_lossy())),
+                Ok(_) => None,
+                Err(err) => Some(Err(err)),
+            })
+            .collect::<LuaResult<_>>()?;
+        linter.lint_lines(&format!("{}.text", joined), &lines, vars);
+    }
+    if let LuaValue::String(name) = tbl.raw_get::<LuaValue>("name")? {
+        linter.lint_lines(&format!("{}.name", joined), &[name.to_string_lossy()], None);
+    }
+
+    let mut children = Vec::new();
+    for pair in tbl.pairs::<LuaValue, LuaValue>() {
+        if let (LuaValue::String(k), LuaValue::Table(child)) = pair? {
+            let k = k.to_string_lossy();
+            if k != "text" {
+                children.push((k, child));
+            }
+        }
+    }
+    children.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    for (k, child) in children {
+        path.push(k);
+        walk_localization(linter, &child, path, loc_vars, seen)?;
+        path.pop();
+    }
+
+    Ok(())
+}
+
+pub(crate) fn lint_localization(
+    lua: &Lua,
+    (loc, opts): (LuaTable, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let loc_vars = match &opts {
+        Some(opts) => opts.get::<Option<LuaTable>>("loc_vars")?,
+        None => None,
+    };
+    let mut linter = Linter {
+        colors: known_colors(lua, opts.as_ref())?,
+        issues: Vec::new(),
+    };
+
+    walk_localization(
+        &mut linter,
+        &loc,
+        &mut Vec::new(),
+        loc_vars.as_ref(),
+        &mut HashSet::new(),
+    )?;
+    linter.to_lua(lua)
+}
+
+// Glyph widths for wrapping: a grapheme -> width table or a function, falling back to a default.
+enum GlyphWidths {
+    Table(LuaTable),
+    Function(LuaFunction),
+    Fixed,
+}
+
+struct WrapMetrics {
+    widths: GlyphWidths,
+    default_width: f64,
+    var_width: f64,
+}
+
+impl WrapMetrics {
+    fn from_opts(opts: Option<&LuaTable>) -> LuaResult<Self> {
+        let Some(opts) = opts else {
+            return Ok(WrapMetrics {
+                widths: GlyphWidths::Fixed,
+                default_width: 1.0,
+                var_width: 2.0,
+            });
+        };
+
+        let widths = match opts.get::<LuaValue>("widths")? {
+            LuaValue::Table(tbl) => GlyphWidths::Table(tbl),
+            LuaValue::Function(func) => GlyphWidths::Function(func),
+            _ => GlyphWidths::Fixed,
+        };
+        let default_width = opts.get::<Option<f64>>("default_width")?.unwrap_or(1.0);
+        let var_width = opts
+            .get::<Option<f64>>("var_width")?
+            .unwrap_or(default_width * 2.0);
+
+        Ok(WrapMetrics {
+            widths,
+            default_width,
+            var_width,
+        })
+    }
+
+    fn measure(&self, grapheme: &str) -> LuaResult<f64> {
+        let width = match &self.widths {
+            GlyphWidths::Table(tbl) => tbl.get::<Option<f64>>(grapheme)?,
+            GlyphWidths::Function(func) => func.call::<Option<f64>>(grapheme)?,
+            GlyphWidths::Fixed => None,
+        };
+
+        Ok(width.unwrap_or(self.default_width))
+    }
+}
+
+// `{s:0.8}` shrinks the text it covers, so it takes proportionally less room.
+fn style_scale(style: Option<&Style>) -> f64 {
+    style
+        .and_then(|style| {
+            style
+                .iter()
+                .rev()
+                .find(|(key, _)| key == "s")
+                .and_then(|(_, value)| value.parse::<f64>().ok())
+        })
+        .unwrap_or(1.0)
+}
+
+#[derive(Clone)]
+struct Piece {
+    style: Option<Style>,
+    text: String,
+    width: f64,
+    var: bool,
+}
+
+enum WrapToken {
+    Word(Vec<Piece>),
+    Space(Piece),
+    Break,
+}
+
+fn word_width(word: &[Piece]) -> f64 {
+    word.iter().map(|piece| piece.width).sum()
+}
+
+fn push_grapheme(word: &mut Vec<Piece>, style: Option<&Style>, grapheme: &str, width: f64) {
+    if let Some(last) = word.last_mut()
+        && !last.var
+        && last.style.as_ref() == style
+    {
+        last.text.push_str(grapheme);
+        last.width += width;
+        return;
+    }
+
+    word.push(Piece {
+        style: style.cloned(),
+        text: grapheme.to_string(),
+        width,
+        var: false,
+    });
+}
+
+struct Tokenizer<'a> {
+    metrics: &'a WrapMetrics,
+    tokens: Vec<WrapToken>,
+    word: Vec<Piece>,
+}
+
+impl Tokenizer<'_> {
+    fn end_word(&mut self) {
+        if !self.word.is_empty() {
+            self.tokens
+                .push(WrapToken::Word(std::mem::take(&mut self.word)));
+        }
+    }
+
+    fn space(&mut self, style: Option<&Style>) -> LuaResult<()> {
+        self.end_word();
+        // Runs of whitespace collapse into one space; a styled one wins so highlight padding stays.
+        if let Some(WrapToken::Space(last)) = self.tokens.last_mut() {
+            if style.is_some() {
+                last.style = style.cloned();
+            }
+        } else if !self.tokens.is_empty() {
+            self.tokens.push(WrapToken::Space(Piece {
+                style: style.cloned(),
+                text: " ".into(),
+                width: self.metrics.measure(" ")? * style_scale(style),
+                var: false,
+            }));
+        }
+
+        Ok(())
+    }
+
+    fn hard_break(&mut self) {
+        self.end_word();
+        if let Some(WrapToken::Space(_)) = self.tokens.last() {
+            self.tokens.pop();
+        }
+        self.tokens.push(WrapToken::Break);
+    }
+
+    fn nodes(&mut self, nodes: Vec<Node>, style: Option<&Style>) -> LuaResult<()> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => {
+                    for grapheme in text.graphemes(true) {
+                        match grapheme {
+                            "\n" | "\r\n" => self.hard_break(),
+                            g if g.chars().all(char::is_whitespace) => self.space(style)?,
+                            g => {
+                                let width = self.metrics.measure(g)? * style_scale(style);
+                                push_grapheme(&mut self.word, style, g, width);
+                            }
+                        }
+                    }
+                }
+                Node::Var(index) => self.word.push(Piece {
+                    style: style.cloned(),
+                    text: format!("#{}#", index),
+                    width: self.metrics.var_width * style_scale(style),
+                    var: true,
+                }),
+                Node::Span(span_style, children) => self.nodes(children, Some(&span_style))?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Cuts a word wider than the line into line-sized chunks, never inside a `#n#` variable.
+fn split_word(
+    word: Vec<Piece>,
+    max_width: f64,
+    metrics: &WrapMetrics,
+) -> LuaResult<Vec<Vec<Piece>>> {
+    let mut chunks: Vec<Vec<Piece>> = vec![Vec::new()];
+    let mut width = 0.0;
+
+    for piece in word {
+        let parts: Vec<(&str, f64)> = if piece.var {
+            vec![(piece.text.as_str(), piece.width)]
+        } else {
+            let scale = style_scale(piece.style.as_ref());
+            piece
+                .text
+                .graphemes(true)
+                .map(|g| Ok((g, metrics.measure(g)? * scale)))
+                .collect::<LuaResult<_>>()?
+        };
+
+        for (text, part_width) in parts {
+            if chunks.last().is_some_and(|chunk| !chunk.is_empty())
+                && width + part_width > max_width
+            {
+                chunks.push(Vec::new());
+                width = 0.0;
+            }
+
+            let chunk = chunks.last_mut().expect("at least one chunk");
+            if piece.var {
+                chunk.push(piece.clone());
+            } else {
+                push_grapheme(chunk, piece.style.as_ref(), text, part_width);
+            }
+            width += part_width;
+        }
+    }
+
+    Ok(chunks)
+}
+
+fn render_style(style: &Style) -> String {
+    let parts: Vec<String> = style
+        .iter()
+        .map(|(key, value)| format!("{}:{}", key, value))
+        .collect();
+
+    format!("{{{}}}", parts.join(","))
+}
+
+// Each line is parsed on its own by the game, so a span still open at a break is reopened at the
+// start of the next line.
+fn render_line(pieces: &[Piece]) -> String {
+    let mut out = String::new();
+    let mut current: Option<&Style> = None;
+
+    for piece in pieces {
+        if piece.style.as_ref() != current {
+            match &piece.style {
+                Some(style) => out.push_str(&render_style(style)),
+                None => out.push_str("{}"),
+            }
+            current = piece.style.as_ref();
+        }
+        out.push_str(&piece.text);
+    }
+
+    out
+}
+
+pub(crate) fn wrap_description(
+    lua: &Lua,
+    (text, max_width, opts): (LuaValue, f64, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    if max_width <= 0.0 {
+        return Err(mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: max_width must be positive, got {}",
+            max_width
+        )));
+    }
+
+    let break_words = match &opts {
+        Some(opts) => opts.get::<Option<bool>>("break_words")?.unwrap_or(true),
+        None => true,
+    };
+    let metrics = WrapMetrics::from_opts(opts.as_ref())?;
+    let mut tokenizer = Tokenizer {
+        metrics: &metrics,
+        tokens: Vec::new(),
+        word: Vec::new(),
+    };
+
+    // Existing description lines are reflowed as one paragraph; "\n" forces a break.
+    for (idx, line) in markup_lines(text)?.iter().enumerate() {
+        if idx > 0 {
+            tokenizer.space(None)?;
+        }
+        tokenizer.nodes(MarkupParser::new(line, None).parse(), None)?;
+    }
+    tokenizer.end_word();
+
+    let mut lines: Vec<Vec<Piece>> = Vec::new();
+    let mut line: Vec<Piece> = Vec::new();
+    let mut width = 0.0;
+    let mut space: Option<Piece> = None;
+
+    for token in tokenizer.tokens {
+        match token {
+            WrapToken::Space(piece) => space = Some(piece),
+            WrapToken::Break => {
+                lines.push(std::mem::take(&mut line));
+                width = 0.0;
+                space = None;
+            }
+            WrapToken::Word(word) => {
+                let ww = word_width(&word);
+                let sw = space.as_ref().map_or(0.0, |space| space.width);
+
+                if !line.is_empty() && width + sw + ww <= max_width {
+                    line.extend(space.take());
+                    line.extend(word);
+                    width += sw + ww;
+                    continue;
+                }
+
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+                space = None;
+
+                let mut chunks = if break_words && ww > max_width {
+                    split_word(word, max_width, &metrics)?
+                } else {
+                    vec![word]
+                };
+                line = chunks.pop().unwrap_or_default();
+                width = word_width(&line);
+                lines.extend(chunks);
+            }
+        }
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lua.create_sequence_from(lines.iter().map(|line| render_line(line)))
+}
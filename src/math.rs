@@ -4,10 +4,80 @@ pub(crate) fn between(_: &Lua, (num, min, max): (f64, f64, f64)) -> LuaResult<bo
     Ok(num >= min && num <= max)
 }
 
+// Deprecated: kept for existing callers, but `|x - y| <= x` is not a tolerance check (it is never
+// true for negative x). Use `approx_eq(a, b, { abs = ..., rel = ... })` instead.
 pub(crate) fn within(_: &Lua, (x, y): (f64, f64)) -> LuaResult<bool> {
     Ok((x - y).abs() <= x)
 }
 
+struct ApproxOpts {
+    abs: f64,
+    rel: f64,
+    ulps: Option<u64>,
+}
+
+impl ApproxOpts {
+    fn from_lua(opts: Option<LuaTable>) -> LuaResult<Self> {
+        let mut approx = ApproxOpts {
+            abs: 1e-9,
+            rel: 1e-9,
+            ulps: None,
+        };
+
+        if let Some(opts) = opts {
+            if let Some(abs) = opts.get::<Option<f64>>("abs")? {
+                approx.abs = abs;
+            }
+            if let Some(rel) = opts.get::<Option<f64>>("rel")? {
+                approx.rel = rel;
+            }
+            approx.ulps = opts.get::<Option<u64>>("ulps")?;
+        }
+
+        Ok(approx)
+    }
+}
+
+// Maps the bit pattern of a float onto a monotonically ordered integer line,
+// so that the distance between two floats can be counted in ULPs.
+fn ordered_bits(num: f64) -> i64 {
+    let bits = num.to_bits() as i64;
+
+    if bits < 0 { i64::MIN - bits } else { bits }
+}
+
+fn ulps_apart(a: f64, b: f64) -> u64 {
+    ordered_bits(a).abs_diff(ordered_bits(b))
+}
+
+pub(crate) fn approx_eq_f64(a: f64, b: f64, abs: f64, rel: f64, ulps: Option<u64>) -> bool {
+    if a.is_nan() || b.is_nan() {
+        return false;
+    }
+
+    if a == b {
+        return true;
+    }
+
+    if a.is_infinite() || b.is_infinite() {
+        return false;
+    }
+
+    let diff = (a - b).abs();
+
+    if diff <= abs || diff <= rel * a.abs().max(b.abs()) {
+        return true;
+    }
+
+    ulps.is_some_and(|max_ulps| ulps_apart(a, b) <= max_ulps)
+}
+
+pub(crate) fn approx_eq(_: &Lua, (a, b, opts): (f64, f64, Option<LuaTable>)) -> LuaResult<bool> {
+    let opts = ApproxOpts::from_lua(opts)?;
+
+    Ok(approx_eq_f64(a, b, opts.abs, opts.rel, opts.ulps))
+}
+
 pub(crate) fn wave_number(_: &Lua, num: f64) -> LuaResult<f64> {
     if num == 0.0 {
         Ok(1.0)
@@ -18,7 +88,15 @@ pub(crate) fn wave_number(_: &Lua, num: f64) -> LuaResult<f64> {
     }
 }
 
-pub(crate) fn clamp(_: &Lua, (num, min, max): (f64, f64, f64)) -> LuaResult<f64> {
+pub(crate) fn clamp_f64(num: f64, min: f64, max: f64) -> LuaResult<f64> {
+    if num.is_nan() || min.is_nan() || max.is_nan() {
+        return Err(mlua::Error::RuntimeError(
+            "[INSOLENCE] Error: clamp called with NaN".into(),
+        ));
+    }
+
+    let (min, max) = if min > max { (max, min) } else { (min, max) };
+
     if num < min {
         Ok(min)
     } else if num > max {
@@ -28,6 +106,64 @@ pub(crate) fn clamp(_: &Lua, (num, min, max): (f64, f64, f64)) -> LuaResult<f64>
     }
 }
 
+pub(crate) fn clamp(_: &Lua, (num, min, max): (f64, f64, f64)) -> LuaResult<f64> {
+    clamp_f64(num, min, max)
+}
+
+pub(crate) fn inverse_lerp_f64(a: f64, b: f64, value: f64) -> LuaResult<f64> {
+    if a == b {
+        return Err(mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: inverse_lerp range {} to {} is empty",
+            a, b
+        )));
+    }
+
+    Ok((value - a) / (b - a))
+}
+
+pub(crate) fn inverse_lerp(_: &Lua, (a, b, value): (f64, f64, f64)) -> LuaResult<f64> {
+    inverse_lerp_f64(a, b, value)
+}
+
+pub(crate) fn remap(
+    _: &Lua,
+    (value, in_min, in_max, out_min, out_max, clamped): (f64, f64, f64, f64, f64, Option<bool>),
+) -> LuaResult<f64> {
+    let mut t = inverse_lerp_f64(in_min, in_max, value)?;
+
+    if clamped.unwrap_or(false) {
+        t = clamp_f64(t, 0.0, 1.0)?;
+    }
+
+    Ok(out_min + (out_max - out_min) * t)
+}
+
+pub(crate) fn wrap(_: &Lua, (value, min, max): (f64, f64, f64)) -> LuaResult<f64> {
+    let (min, max) = if min > max { (max, min) } else { (min, max) };
+    let range = max - min;
+
+    if range == 0.0 {
+        return Ok(min);
+    }
+
+    Ok(min + (value - min).rem_euclid(range))
+}
+
+pub(crate) fn snap(_: &Lua, (value, step, offset): (f64, f64, Option<f64>)) -> LuaResult<f64> {
+    let offset = offset.unwrap_or(0.0);
+
+    if step == 0.0 {
+        return Ok(value);
+    }
+
+    Ok(((value - offset) / step).round() * step + offset)
+}
+
+// Largest power at which every whole f64 is still exact.
+const MAX_EXACT_POWER: f64 = 9_007_199_254_740_992.0;
+
+// Square-and-multiply for whole powers. Fractional, infinite, NaN and huge powers go to `powf`,
+// since halving them never reaches zero.
 pub(crate) fn exponentiate(lua: &Lua, (mut base, mut power): (f64, f64)) -> LuaResult<f64> {
     if power == 0.0 {
         return Ok(1.0);
@@ -37,6 +173,10 @@ pub(crate) fn exponentiate(lua: &Lua, (mut base, mut power): (f64, f64)) -> LuaR
         return Ok(1.0 / exponentiate(lua, (base, -power))?);
     }
 
+    if !power.is_finite() || power.fract() != 0.0 || power > MAX_EXACT_POWER {
+        return Ok(base.powf(power));
+    }
+
     let mut result = 1.0;
 
     while power > 0.0 {
@@ -50,3 +190,542 @@ pub(crate) fn exponentiate(lua: &Lua, (mut base, mut power): (f64, f64)) -> LuaR
 
     Ok(result)
 }
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ExprFunc {
+    Clamp,
+    Exponentiate,
+    Between,
+    Remap,
+    InverseLerp,
+    Wrap,
+    Snap,
+    Min,
+    Max,
+    Abs,
+    Floor,
+    Ceil,
+    Round,
+    Sqrt,
+}
+
+impl ExprFunc {
+    fn lookup(name: &str) -> Option<(ExprFunc, usize, usize)> {
+        Some(match name {
+            "clamp" => (ExprFunc::Clamp, 3, 3),
+            "exponentiate" => (ExprFunc::Exponentiate, 2, 2),
+            "between" => (ExprFunc::Between, 3, 3),
+            "remap" => (ExprFunc::Remap, 5, 5),
+            "inverse_lerp" => (ExprFunc::InverseLerp, 3, 3),
+            "wrap" => (ExprFunc::Wrap, 3, 3),
+            "snap" => (ExprFunc::Snap, 2, 3),
+            "min" => (ExprFunc::Min, 1, usize::MAX),
+            "max" => (ExprFunc::Max, 1, usize::MAX),
+            "abs" => (ExprFunc::Abs, 1, 1),
+            "floor" => (ExprFunc::Floor, 1, 1),
+            "ceil" => (ExprFunc::Ceil, 1, 1),
+            "round" => (ExprFunc::Round, 1, 1),
+            "sqrt" => (ExprFunc::Sqrt, 1, 1),
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+enum ExprNode {
+    Num(f64),
+    Var(Vec<String>),
+    Neg(Box<ExprNode>),
+    Binary(BinOp, Box<ExprNode>, Box<ExprNode>),
+    Call(ExprFunc, Vec<ExprNode>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Num(f64),
+    Ident(String),
+    Op(BinOp),
+    LParen,
+    RParen,
+    Comma,
+}
+
+fn expr_error(source: &str, pos: usize, msg: &str) -> mlua::Error {
+    mlua::Error::RuntimeError(format!(
+        "[INSOLENCE] Error: {} at position {} in expression \"{}\"",
+        msg,
+        pos + 1,
+        source
+    ))
+}
+
+fn tokenize(source: &str) -> LuaResult<Vec<(Token, usize)>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let ch = chars[pos];
+        let start = pos;
+
+        if ch.is_whitespace() {
+            pos += 1;
+            continue;
+        }
+
+        if ch.is_ascii_digit()
+            || (ch == '.' && chars.get(pos + 1).is_some_and(char::is_ascii_digit))
+        {
+            while pos < chars.len() && (chars[pos].is_ascii_digit() || chars[pos] == '.') {
+                pos += 1;
+            }
+            if pos < chars.len() && (chars[pos] == 'e' || chars[pos] == 'E') {
+                pos += 1;
+                if pos < chars.len() && (chars[pos] == '+' || chars[pos] == '-') {
+                    pos += 1;
+                }
+                while pos < chars.len() && chars[pos].is_ascii_digit() {
+                    pos += 1;
+                }
+            }
+
+            let text: String = chars[start..pos].iter().collect();
+            let num = text
+                .parse::<f64>()
+                .map_err(|_| expr_error(source, start, &format!("invalid number '{}'", text)))?;
+            tokens.push((Token::Num(num), start));
+            continue;
+        }
+
+        if ch.is_alphabetic() || ch == '_' {
+            while pos < chars.len()
+                && (chars[pos].is_alphanumeric() || chars[pos] == '_' || chars[pos] == '.')
+            {
+                pos += 1;
+            }
+
+            let text: String = chars[start..pos].iter().collect();
+            if text.ends_with('.') || text.contains("..") {
+                return Err(expr_error(
+                    source,
+                    start,
+                    &format!("invalid name '{}'", text),
+                ));
+            }
+            tokens.push((Token::Ident(text), start));
+            continue;
+        }
+
+        let next = chars.get(pos + 1).copied();
+        let (token, width) = match (ch, next) {
+            ('<', Some('=')) => (Token::Op(BinOp::Le), 2),
+            ('>', Some('=')) => (Token::Op(BinOp::Ge), 2),
+            ('=', Some('=')) => (Token::Op(BinOp::Eq), 2),
+            ('~', Some('=')) | ('!', Some('=')) => (Token::Op(BinOp::Ne), 2),
+            ('<', _) => (Token::Op(BinOp::Lt), 1),
+            ('>', _) => (Token::Op(BinOp::Gt), 1),
+            ('+', _) => (Token::Op(BinOp::Add), 1),
+            ('-', _) => (Token::Op(BinOp::Sub), 1),
+            ('*', _) => (Token::Op(BinOp::Mul), 1),
+            ('/', _) => (Token::Op(BinOp::Div), 1),
+            ('%', _) => (Token::Op(BinOp::Mod), 1),
+            ('^', _) => (Token::Op(BinOp::Pow), 1),
+            ('(', _) => (Token::LParen, 1),
+            (')', _) => (Token::RParen, 1),
+            (',', _) => (Token::Comma, 1),
+            _ => {
+                return Err(expr_error(
+                    source,
+                    start,
+                    &format!("unexpected character '{}'", ch),
+                ));
+            }
+        };
+
+        tokens.push((token, start));
+        pos += width;
+    }
+
+    Ok(tokens)
+}
+
+// Bounds parser recursion (and so evaluation depth) for hostile config strings.
+const MAX_EXPR_DEPTH: usize = 512;
+
+struct ExprParser<'a> {
+    source: &'a str,
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    depth: usize,
+}
+
+impl ExprParser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(tok, _)| tok)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map(|(_, at)| *at)
+            .unwrap_or(self.source.chars().count())
+    }
+
+    fn error(&self, msg: &str) -> mlua::Error {
+        expr_error(self.source, self.offset(), msg)
+    }
+
+    fn enter(&mut self) -> LuaResult<()> {
+        self.depth += 1;
+        if self.depth > MAX_EXPR_DEPTH {
+            return Err(self.error("expression nested too deeply"));
+        }
+        Ok(())
+    }
+
+    fn expect(&mut self, token: Token, what: &str) -> LuaResult<()> {
+        if self.peek() == Some(&token) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {}", what)))
+        }
+    }
+
+    fn binary_level(
+        &mut self,
+        ops: &[BinOp],
+        next: fn(&mut Self) -> LuaResult<ExprNode>,
+    ) -> LuaResult<ExprNode> {
+        let mut lhs = next(self)?;
+        // Each operator in a chain deepens the left-leaning tree by one level.
+        let entered = self.depth;
+
+        while let Some(Token::Op(op)) = self.peek() {
+            let op = *op;
+            if !ops.contains(&op) {
+                break;
+            }
+
+            self.enter()?;
+            self.pos += 1;
+            let rhs = next(self)?;
+            lhs = ExprNode::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+
+        self.depth = entered;
+        Ok(lhs)
+    }
+
+    fn comparison(&mut self) -> LuaResult<ExprNode> {
+        self.binary_level(
+            &[
+                BinOp::Lt,
+                BinOp::Le,
+                BinOp::Gt,
+                BinOp::Ge,
+                BinOp::Eq,
+                BinOp::Ne,
+            ],
+            Self::additive,
+        )
+    }
+
+    fn additive(&mut self) -> LuaResult<ExprNode> {
+        self.binary_level(&[BinOp::Add, BinOp::Sub], Self::multiplicative)
+    }
+
+    fn multiplicative(&mut self) -> LuaResult<ExprNode> {
+        self.binary_level(&[BinOp::Mul, BinOp::Div, BinOp::Mod], Self::unary)
+    }
+
+    // Unary minus binds looser than `^`, matching Lua: `-2 ^ 2` is `-4`.
+    fn unary(&mut self) -> LuaResult<ExprNode> {
+        match self.peek() {
+            Some(Token::Op(BinOp::Sub)) => {
+                self.pos += 1;
+                self.enter()?;
+                let operand = self.unary()?;
+                self.depth -= 1;
+                Ok(ExprNode::Neg(Box::new(operand)))
+            }
+            Some(Token::Op(BinOp::Add)) => {
+                self.pos += 1;
+                self.enter()?;
+                let operand = self.unary()?;
+                self.depth -= 1;
+                Ok(operand)
+            }
+            _ => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> LuaResult<ExprNode> {
+        let base = self.primary()?;
+
+        if self.peek() == Some(&Token::Op(BinOp::Pow)) {
+            self.pos += 1;
+            self.enter()?;
+            let exponent = self.unary()?;
+            self.depth -= 1;
+            return Ok(ExprNode::Binary(
+                BinOp::Pow,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> LuaResult<ExprNode> {
+        match self.peek().cloned() {
+            Some(Token::Num(n)) => {
+                self.pos += 1;
+                Ok(ExprNode::Num(n))
+            }
+            Some(Token::LParen) => {
+                self.pos += 1;
+                self.enter()?;
+                let inner = self.comparison()?;
+                self.expect(Token::RParen, "')'")?;
+                self.depth -= 1;
+                Ok(inner)
+            }
+            Some(Token::Ident(name)) => {
+                let at = self.offset();
+                self.pos += 1;
+
+                if self.peek() != Some(&Token::LParen) {
+                    return Ok(ExprNode::Var(name.split('.').map(String::from).collect()));
+                }
+
+                let (func, min_args, max_args) = ExprFunc::lookup(&name).ok_or_else(|| {
+                    expr_error(self.source, at, &format!("unknown function '{}'", name))
+                })?;
+
+                self.pos += 1;
+                self.enter()?;
+                let mut args = Vec::new();
+                if self.peek() != Some(&Token::RParen) {
+                    loop {
+                        args.push(self.comparison()?);
+                        if self.peek() == Some(&Token::Comma) {
+                            self.pos += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                self.expect(Token::RParen, "')'")?;
+                self.depth -= 1;
+
+                if args.len() < min_args || args.len() > max_args {
+                    return Err(expr_error(
+                        self.source,
+                        at,
+                        &format!("wrong number of arguments to '{}'", name),
+                    ));
+                }
+
+                Ok(ExprNode::Call(func, args))
+            }
+            _ => Err(self.error("expected a number, name or '('")),
+        }
+    }
+}
+
+fn bool_num(cond: bool) -> f64 {
+    if cond { 1.0 } else { 0.0 }
+}
+
+impl ExprNode {
+    fn eval(&self, lua: &Lua, vars: &dyn Fn(&[String]) -> LuaResult<f64>) -> LuaResult<f64> {
+        Ok(match self {
+            ExprNode::Num(n) => *n,
+            ExprNode::Var(path) => vars(path)?,
+            ExprNode::Neg(inner) => -inner.eval(lua, vars)?,
+            ExprNode::Binary(op, lhs, rhs) => {
+                let a = lhs.eval(lua, vars)?;
+                let b = rhs.eval(lua, vars)?;
+
+                match op {
+                    BinOp::Add => a + b,
+                    BinOp::Sub => a - b,
+                    BinOp::Mul => a * b,
+                    BinOp::Div => a / b,
+                    BinOp::Mod => a - (a / b).floor() * b,
+                    BinOp::Pow => a.powf(b),
+                    BinOp::Lt => bool_num(a < b),
+                    BinOp::Le => bool_num(a <= b),
+                    BinOp::Gt => bool_num(a > b),
+                    BinOp::Ge => bool_num(a >= b),
+                    BinOp::Eq => bool_num(a == b),
+                    BinOp::Ne => bool_num(a != b),
+                }
+            }
+            ExprNode::Call(func, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.eval(lua, vars))
+                    .collect::<LuaResult<Vec<f64>>>()?;
+
+                match func {
+                    ExprFunc::Clamp => clamp_f64(args[0], args[1], args[2])?,
+                    ExprFunc::Exponentiate => exponentiate(lua, (args[0], args[1]))?,
+                    ExprFunc::Between => bool_num(between(lua, (args[0], args[1], args[2]))?),
+                    ExprFunc::Remap => {
+                        remap(lua, (args[0], args[1], args[2], args[3], args[4], None))?
+                    }
+                    ExprFunc::InverseLerp => inverse_lerp_f64(args[0], args[1], args[2])?,
+                    ExprFunc::Wrap => wrap(lua, (args[0], args[1], args[2]))?,
+                    ExprFunc::Snap => snap(lua, (args[0], args[1], args.get(2).copied()))?,
+                    ExprFunc::Min => args.iter().copied().fold(f64::INFINITY, f64::min),
+                    ExprFunc::Max => args.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                    ExprFunc::Abs => args[0].abs(),
+                    ExprFunc::Floor => args[0].floor(),
+                    ExprFunc::Ceil => args[0].ceil(),
+                    ExprFunc::Round => args[0].round(),
+                    ExprFunc::Sqrt => args[0].sqrt(),
+                }
+            }
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct CompiledExpr {
+    source: String,
+    root: ExprNode,
+}
+
+impl CompiledExpr {
+    pub(crate) fn parse(source: &str) -> LuaResult<Self> {
+        let mut parser = ExprParser {
+            source,
+            tokens: tokenize(source)?,
+            pos: 0,
+            depth: 0,
+        };
+
+        let root = parser.comparison()?;
+        if parser.peek().is_some() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+
+        Ok(CompiledExpr {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    // Variables are only ever read from `vars`; globals are never consulted.
+    pub(crate) fn eval(&self, lua: &Lua, vars: Option<&LuaTable>) -> LuaResult<f64> {
+        let lookup = |path: &[String]| -> LuaResult<f64> {
+            let mut current = match vars {
+                Some(vars) => LuaValue::Table(vars.clone()),
+                None => LuaValue::Nil,
+            };
+
+            for segment in path {
+                current = match current {
+                    LuaValue::Table(tbl) => tbl.raw_get::<LuaValue>(segment.as_str())?,
+                    _ => LuaValue::Nil,
+                };
+            }
+
+            match current {
+                LuaValue::Number(n) => Ok(n),
+                LuaValue::Integer(n) => Ok(n as f64),
+                LuaValue::Boolean(b) => Ok(bool_num(b)),
+                other => Err(mlua::Error::RuntimeError(format!(
+                    "[INSOLENCE] Error: variable '{}' in expression \"{}\" is not a number (got {})",
+                    path.join("."),
+                    self.source,
+                    other.type_name()
+                ))),
+            }
+        };
+
+        self.root.eval(lua, &lookup)
+    }
+}
+
+impl LuaUserData for CompiledExpr {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("source", |_, this| Ok(this.source.clone()));
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("eval", |lua, this, vars: Option<LuaTable>| {
+            this.eval(lua, vars.as_ref())
+        });
+        methods.add_meta_method(LuaMetaMethod::Call, |lua, this, vars: Option<LuaTable>| {
+            this.eval(lua, vars.as_ref())
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!("Expr({})", this.source))
+        });
+    }
+}
+
+pub(crate) fn compile_expr(_: &Lua, source: String) -> LuaResult<CompiledExpr> {
+    CompiledExpr::parse(&source)
+}
+
+pub(crate) fn eval_expr(lua: &Lua, (source, vars): (String, Option<LuaTable>)) -> LuaResult<f64> {
+    CompiledExpr::parse(&source)?.eval(lua, vars.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exponentiate_handles_whole_powers() {
+        let lua = Lua::new();
+
+        assert_eq!(exponentiate(&lua, (3.0, 5.0)).unwrap(), 243.0);
+        assert_eq!(exponentiate(&lua, (2.0, -2.0)).unwrap(), 0.25);
+        assert_eq!(exponentiate(&lua, (7.0, 0.0)).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn exponentiate_terminates_on_non_finite_and_huge_powers() {
+        let lua = Lua::new();
+
+        assert_eq!(
+            exponentiate(&lua, (2.0, f64::INFINITY)).unwrap(),
+            f64::INFINITY
+        );
+        assert_eq!(exponentiate(&lua, (2.0, f64::NEG_INFINITY)).unwrap(), 0.0);
+        assert!(exponentiate(&lua, (2.0, f64::NAN)).unwrap().is_nan());
+        assert_eq!(exponentiate(&lua, (2.0, 1e300)).unwrap(), f64::INFINITY);
+        assert_eq!(exponentiate(&lua, (0.5, 1e300)).unwrap(), 0.0);
+        assert_eq!(exponentiate(&lua, (4.0, 0.5)).unwrap(), 2.0);
+    }
+
+    #[test]
+    fn eval_expr_with_infinite_exponent_returns() {
+        let lua = Lua::new();
+        let result = eval_expr(&lua, ("exponentiate(2, 1e400)".into(), None)).unwrap();
+
+        assert_eq!(result, f64::INFINITY);
+    }
+}
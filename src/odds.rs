@@ -0,0 +1,491 @@
+use crate::poker::{HandFlags, PlayingCard, rank_from_value};
+use crate::tbl::{PathSegment, as_number, lookup_path};
+use mlua::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+const VANILLA_SUITS: [&str; 4] = ["Spades", "Hearts", "Clubs", "Diamonds"];
+
+// Pascal's triangle, truncated to the columns a draw can actually reach.
+struct Binomials {
+    rows: Vec<Vec<f64>>,
+}
+
+impl Binomials {
+    fn new(max_n: usize, max_k: usize) -> Self {
+        let mut rows = vec![vec![0.0; max_k + 1]; max_n + 1];
+
+        for n in 0..=max_n {
+            rows[n][0] = 1.0;
+            for k in 1..=max_k.min(n) {
+                rows[n][k] = rows[n - 1][k - 1] + if k < n { rows[n - 1][k] } else { 0.0 };
+            }
+        }
+
+        Binomials { rows }
+    }
+
+    fn choose(&self, n: usize, k: usize) -> f64 {
+        if k > n { 0.0 } else { self.rows[n][k] }
+    }
+}
+
+// Counts the ways to draw exactly `draws` cards, where `categories` holds (in deck, already held)
+// pairs and any remaining deck cards are indistinct filler, and returns the number of draws whose
+// final state satisfies `goal`.
+fn count_draws<S: Copy + Eq + Hash>(
+    binom: &Binomials,
+    categories: &[(usize, usize)],
+    filler: usize,
+    draws: usize,
+    init: S,
+    step: impl Fn(S, usize) -> S,
+    goal: impl Fn(S) -> bool,
+) -> f64 {
+    let mut states: HashMap<(usize, S), f64> = HashMap::new();
+    states.insert((0, init), 1.0);
+
+    for &(in_deck, held) in categories {
+        let mut next: HashMap<(usize, S), f64> = HashMap::new();
+
+        for ((drawn, state), ways) in states {
+            for take in 0..=in_deck.min(draws - drawn) {
+                let key = (drawn + take, step(state, held + take));
+                *next.entry(key).or_insert(0.0) += ways * binom.choose(in_deck, take);
+            }
+        }
+
+        states = next;
+    }
+
+    states
+        .into_iter()
+        .filter(|((_, state), _)| goal(*state))
+        .map(|((drawn, _), ways)| ways * binom.choose(filler, draws - drawn))
+        .sum()
+}
+
+fn hypergeometric_pmf(
+    binom: &Binomials,
+    population: usize,
+    successes: usize,
+    draws: usize,
+    k: usize,
+) -> f64 {
+    if k > draws || k > successes || draws - k > population - successes {
+        return 0.0;
+    }
+
+    binom.choose(successes, k) * binom.choose(population - successes, draws - k)
+        / binom.choose(population, draws)
+}
+
+// Rounding can push a summed probability just past 1, and an empty sum is -0.0.
+fn probability(sum: f64) -> f64 {
+    (sum + 0.0).min(1.0)
+}
+
+fn at_least(population: usize, successes: usize, draws: usize, needed: usize) -> f64 {
+    let binom = Binomials::new(population, draws);
+
+    probability(
+        (needed..=draws.min(successes))
+            .map(|k| hypergeometric_pmf(&binom, population, successes, draws, k))
+            .sum(),
+    )
+}
+
+pub(crate) fn hypergeometric(
+    _: &Lua,
+    (population, successes, draws, k, mode): (usize, usize, usize, usize, Option<String>),
+) -> LuaResult<f64> {
+    if successes > population || draws > population {
+        return Err(mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: cannot draw {} cards with {} successes from a population of {}",
+            draws, successes, population
+        )));
+    }
+
+    let binom = Binomials::new(population, draws);
+    let pmf = |k: usize| hypergeometric_pmf(&binom, population, successes, draws, k);
+    let max_k = draws.min(successes);
+
+    match mode.as_deref().unwrap_or("at_least") {
+        "exactly" => Ok(pmf(k)),
+        "at_least" => Ok(probability((k..=max_k).map(pmf).sum())),
+        "at_most" => Ok(probability((0..=k.min(max_k)).map(pmf).sum())),
+        other => Err(mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: unknown hypergeometric mode {}",
+            other
+        ))),
+    }
+}
+
+pub(crate) struct DrawState {
+    pub(crate) deck: Vec<PlayingCard>,
+    pub(crate) hand: Vec<PlayingCard>,
+    pub(crate) flags: HandFlags,
+}
+
+fn game_cards(lua: &Lua, area: &str) -> LuaResult<Option<LuaTable>> {
+    let path = [
+        PathSegment::Key("G".into()),
+        PathSegment::Key(area.into()),
+        PathSegment::Key("cards".into()),
+    ];
+
+    match lookup_path(LuaValue::Table(lua.globals()), &path)? {
+        LuaValue::Table(cards) => Ok(Some(cards)),
+        _ => Ok(None),
+    }
+}
+
+fn parse_cards(cards: Option<LuaTable>) -> LuaResult<Vec<PlayingCard>> {
+    match cards {
+        Some(cards) => cards
+            .sequence_values::<LuaTable>()
+            .map(|card| PlayingCard::from_lua(&card?))
+            .collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
+impl DrawState {
+    fn from_lua(lua: &Lua, opts: Option<&LuaTable>) -> LuaResult<Self> {
+        let mut flags = HandFlags::default();
+        let mut deck = None;
+        let mut hand = None;
+
+        if let Some(opts) = opts {
+            deck = opts.get::<Option<LuaTable>>("deck")?;
+            hand = opts.get::<Option<LuaTable>>("hand")?;
+            flags.four_fingers = opts.get::<Option<bool>>("four_fingers")?.unwrap_or(false);
+            flags.shortcut = opts.get::<Option<bool>>("shortcut")?.unwrap_or(false);
+            flags.smeared = opts.get::<Option<bool>>("smeared")?.unwrap_or(false);
+        }
+
+        let deck = match deck {
+            Some(deck) => Some(deck),
+            None => game_cards(lua, "deck")?,
+        }
+        .ok_or_else(|| {
+            mlua::Error::RuntimeError(
+                "[INSOLENCE] Error: no deck given and G.deck.cards is unavailable".into(),
+            )
+        })?;
+
+        let hand = match hand {
+            Some(hand) => Some(hand),
+            None => game_cards(lua, "hand")?,
+        };
+
+        Ok(DrawState {
+            deck: parse_cards(Some(deck))?,
+            hand: parse_cards(hand)?,
+            flags,
+        })
+    }
+
+    fn check_draws(&self, draws: usize) -> LuaResult<()> {
+        if draws > self.deck.len() {
+            return Err(mlua::Error::RuntimeError(format!(
+                "[INSOLENCE] Error: cannot draw {} cards from a deck of {}",
+                draws,
+                self.deck.len()
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn suit_key<'a>(&self, suit: &'a str) -> &'a str {
+        match suit {
+            "Diamonds" if self.flags.smeared => "Hearts",
+            "Clubs" if self.flags.smeared => "Spades",
+            other => other,
+        }
+    }
+
+    fn matches_suit(&self, card: &PlayingCard, suit: &str) -> bool {
+        !card.stone
+            && (card.wild
+                || card
+                    .suit
+                    .as_deref()
+                    .is_some_and(|own| self.suit_key(own) == self.suit_key(suit)))
+    }
+
+    pub(crate) fn suit_odds(&self, suit: &str, needed: usize, draws: usize) -> f64 {
+        let successes = self
+            .deck
+            .iter()
+            .filter(|card| self.matches_suit(card, suit))
+            .count();
+
+        at_least(self.deck.len(), successes, draws, needed)
+    }
+
+    pub(crate) fn rank_odds(&self, rank: u8, needed: usize, draws: usize) -> f64 {
+        let successes = self
+            .deck
+            .iter()
+            .filter(|card| card.rank == Some(rank))
+            .count();
+
+        at_least(self.deck.len(), successes, draws, needed)
+    }
+
+    fn rank_categories(&self, ranks: impl Iterator<Item = u8>) -> Vec<(usize, usize)> {
+        ranks
+            .map(|rank| {
+                let count = |cards: &[PlayingCard]| {
+                    cards.iter().filter(|card| card.rank == Some(rank)).count()
+                };
+                (count(&self.deck), count(&self.hand))
+            })
+            .collect()
+    }
+
+    fn all_ranks(&self) -> Vec<u8> {
+        let mut ranks: Vec<u8> = self
+            .deck
+            .iter()
+            .chain(&self.hand)
+            .filter_map(|card| card.rank)
+            .collect();
+        ranks.sort_unstable();
+        ranks.dedup();
+        ranks
+    }
+
+    fn flush_ways(&self, binom: &Binomials, draws: usize) -> f64 {
+        let needed = if self.flags.four_fingers { 4 } else { 5 };
+
+        let mut suits: Vec<&str> = Vec::new();
+        for card in self.deck.iter().chain(&self.hand) {
+            if let Some(suit) = card.suit.as_deref()
+                && !card.wild
+                && !card.stone
+            {
+                let key = self.suit_key(suit);
+                if !suits.contains(&key) {
+                    suits.push(key);
+                }
+            }
+        }
+        for suit in VANILLA_SUITS {
+            let key = self.suit_key(suit);
+            if !suits.contains(&key) {
+                suits.push(key);
+            }
+        }
+
+        let natural = |cards: &[PlayingCard], suit: &str| {
+            cards
+                .iter()
+                .filter(|card| {
+                    !card.wild
+                        && !card.stone
+                        && card.suit.as_deref().map(|own| self.suit_key(own)) == Some(suit)
+                })
+                .count()
+        };
+        let categories: Vec<(usize, usize)> = suits
+            .iter()
+            .map(|suit| (natural(&self.deck, suit), natural(&self.hand, suit)))
+            .collect();
+
+        let deck_wilds = self.deck.iter().filter(|c| c.wild && !c.stone).count();
+        let held_wilds = self.hand.iter().filter(|c| c.wild && !c.stone).count();
+        let filler = self.deck.len() - deck_wilds - categories.iter().map(|c| c.0).sum::<usize>();
+
+        (0..=deck_wilds.min(draws))
+            .map(|wilds| {
+                let bonus = held_wilds + wilds;
+                binom.choose(deck_wilds, wilds)
+                    * count_draws(
+                        binom,
+                        &categories,
+                        filler,
+                        draws - wilds,
+                        false,
+                        |done, count| done || count + bonus >= needed,
+                        |done| done,
+                    )
+            })
+            .sum()
+    }
+
+    fn straight_ways(&self, binom: &Binomials, draws: usize) -> f64 {
+        let needed = if self.flags.four_fingers { 4 } else { 5 };
+        let shortcut = self.flags.shortcut;
+
+        // (run length, skipped the previous rank, straight found)
+        let step = move |(run, skipped, done): (usize, bool, bool), count: usize| {
+            if count > 0 {
+                let run = run + 1;
+                (run, false, done || run >= needed)
+            } else if shortcut && !skipped && run > 0 {
+                (run, true, done)
+            } else {
+                (0, false, done)
+            }
+        };
+
+        let categories = self.rank_categories(2..=13);
+        let (deck_aces, held_aces) = self.rank_categories(std::iter::once(14))[0];
+        let filler = self.deck.len() - deck_aces - categories.iter().map(|c| c.0).sum::<usize>();
+
+        // Aces sit at both ends of the run, so condition on how many are drawn.
+        (0..=deck_aces.min(draws))
+            .map(|aces| {
+                let ace_count = held_aces + aces;
+                let init = step((0, false, false), ace_count);
+                let mut with_high_ace = categories.clone();
+                with_high_ace.push((0, ace_count));
+
+                binom.choose(deck_aces, aces)
+                    * count_draws(
+                        binom,
+                        &with_high_ace,
+                        filler,
+                        draws - aces,
+                        init,
+                        step,
+                        |(_, _, done)| done,
+                    )
+            })
+            .sum()
+    }
+
+    pub(crate) fn hand_odds(&self, hand: &str, draws: usize) -> LuaResult<f64> {
+        let binom = Binomials::new(self.deck.len(), draws);
+        let ranks = self.all_ranks();
+        let categories = self.rank_categories(ranks.iter().copied());
+        let filler = self.deck.len() - categories.iter().map(|c| c.0).sum::<usize>();
+
+        let of_a_kind = |size: usize| {
+            count_draws(
+                &binom,
+                &categories,
+                filler,
+                draws,
+                false,
+                move |done, count| done || count >= size,
+                |done| done,
+            )
+        };
+
+        let ways = match hand {
+            "Pair" => of_a_kind(2),
+            "Three of a Kind" => of_a_kind(3),
+            "Four of a Kind" => of_a_kind(4),
+            "Five of a Kind" => of_a_kind(5),
+            "Two Pair" => count_draws(
+                &binom,
+                &categories,
+                filler,
+                draws,
+                0usize,
+                |pairs, count| (pairs + usize::from(count >= 2)).min(2),
+                |pairs| pairs >= 2,
+            ),
+            "Full House" => count_draws(
+                &binom,
+                &categories,
+                filler,
+                draws,
+                (0usize, 0usize),
+                |(trips, pairs), count| {
+                    (
+                        (trips + usize::from(count >= 3)).min(1),
+                        (pairs + usize::from(count >= 2)).min(2),
+                    )
+                },
+                |(trips, pairs)| trips >= 1 && pairs >= 2,
+            ),
+            "Flush" => self.flush_ways(&binom, draws),
+            "Straight" => self.straight_ways(&binom, draws),
+            other => {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "[INSOLENCE] Error: draw odds are not available for {}",
+                    other
+                )));
+            }
+        };
+
+        Ok(probability(ways / binom.choose(self.deck.len(), draws)))
+    }
+}
+
+fn rank_arg(rank: &LuaValue) -> LuaResult<u8> {
+    let parsed = match rank {
+        LuaValue::String(value) => rank_from_value(&value.to_str()?),
+        other => as_number(other).map(|id| id as u8),
+    };
+
+    parsed.ok_or_else(|| {
+        mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: {} is not a card rank",
+            rank.to_string().unwrap_or_default()
+        ))
+    })
+}
+
+pub(crate) fn suit_draw_odds(
+    lua: &Lua,
+    (suit, needed, draws, opts): (String, usize, usize, Option<LuaTable>),
+) -> LuaResult<f64> {
+    let state = DrawState::from_lua(lua, opts.as_ref())?;
+    state.check_draws(draws)?;
+
+    Ok(state.suit_odds(&suit, needed, draws))
+}
+
+pub(crate) fn rank_draw_odds(
+    lua: &Lua,
+    (rank, needed, draws, opts): (LuaValue, usize, usize, Option<LuaTable>),
+) -> LuaResult<f64> {
+    let rank = rank_arg(&rank)?;
+    let state = DrawState::from_lua(lua, opts.as_ref())?;
+    state.check_draws(draws)?;
+
+    Ok(state.rank_odds(rank, needed, draws))
+}
+
+pub(crate) fn hand_draw_odds(
+    lua: &Lua,
+    (hand, draws, opts): (String, usize, Option<LuaTable>),
+) -> LuaResult<f64> {
+    let state = DrawState::from_lua(lua, opts.as_ref())?;
+    state.check_draws(draws)?;
+
+    state.hand_odds(&hand, draws)
+}
+
+pub(crate) fn deck_composition(lua: &Lua, opts: Option<LuaTable>) -> LuaResult<LuaTable> {
+    let state = DrawState::from_lua(lua, opts.as_ref())?;
+    let mut suits: BTreeMap<String, usize> = BTreeMap::new();
+    let mut ranks: BTreeMap<u8, usize> = BTreeMap::new();
+
+    for card in &state.deck {
+        if card.stone {
+            continue;
+        }
+        if let Some(suit) = &card.suit {
+            *suits.entry(suit.clone()).or_insert(0) += 1;
+        }
+        if let Some(rank) = card.rank {
+            *ranks.entry(rank).or_insert(0) += 1;
+        }
+    }
+
+    let result = lua.create_table()?;
+    result.set("size", state.deck.len())?;
+    result.set("suits", lua.create_table_from(suits)?)?;
+    result.set("ranks", lua.create_table_from(ranks)?)?;
+    result.set("wild", state.deck.iter().filter(|c| c.wild).count())?;
+    result.set("stone", state.deck.iter().filter(|c| c.stone).count())?;
+
+    Ok(result)
+}
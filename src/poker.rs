@@ -0,0 +1,388 @@
+use crate::tbl::{lookup_path, parse_path};
+use mlua::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+
+const CUSTOM_HANDS_KEY: &str = "insolence_poker_hands";
+
+pub(crate) const HAND_ORDER: [&str; 12] = [
+    "Flush Five",
+    "Flush House",
+    "Five of a Kind",
+    "Straight Flush",
+    "Four of a Kind",
+    "Full House",
+    "Flush",
+    "Straight",
+    "Three of a Kind",
+    "Two Pair",
+    "Pair",
+    "High Card",
+];
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct HandFlags {
+    pub(crate) four_fingers: bool,
+    pub(crate) shortcut: bool,
+    pub(crate) smeared: bool,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct PlayingCard {
+    pub(crate) rank: Option<u8>,
+    pub(crate) suit: Option<String>,
+    pub(crate) wild: bool,
+    pub(crate) stone: bool,
+}
+
+pub(crate) fn rank_from_value(value: &str) -> Option<u8> {
+    match value {
+        "Ace" => Some(14),
+        "King" => Some(13),
+        "Queen" => Some(12),
+        "Jack" => Some(11),
+        other => other.parse::<u8>().ok(),
+    }
+}
+
+fn smeared_suit(suit: &str) -> &str {
+    match suit {
+        "Diamonds" => "Hearts",
+        "Clubs" => "Spades",
+        other => other,
+    }
+}
+
+fn string_at(card: &LuaTable, path: &str) -> LuaResult<Option<String>> {
+    match lookup_path(LuaValue::Table(card.clone()), &parse_path(path))? {
+        LuaValue::String(key) => Ok(Some(key.to_str()?.to_string())),
+        _ => Ok(None),
+    }
+}
+
+impl PlayingCard {
+    pub(crate) fn from_lua(card: &LuaTable) -> LuaResult<Self> {
+        let key = string_at(card, "config.center.key")?;
+        let ability_name = string_at(card, "ability.name")?;
+
+        let is = |center: &str, name: &str| {
+            key.as_deref() == Some(center) || ability_name.as_deref() == Some(name)
+        };
+
+        let stone = is("m_stone", "Stone Card");
+        let wild = is("m_wild", "Wild Card");
+
+        let base = card.get::<Option<LuaTable>>("base")?;
+        let (rank, suit) = match base {
+            Some(base) if !stone => {
+                let rank = match base.get::<Option<f64>>("id")? {
+                    Some(id) => Some(id as u8),
+                    None => base
+                        .get::<Option<String>>("value")?
+                        .and_then(|value| rank_from_value(&value)),
+                };
+
+                (rank, base.get::<Option<String>>("suit")?)
+            }
+            _ => (None, None),
+        };
+
+        Ok(PlayingCard {
+            rank,
+            suit,
+            wild,
+            stone,
+        })
+    }
+
+    fn has_suit(&self, suit: &str, flags: HandFlags) -> bool {
+        if self.stone {
+            return false;
+        }
+
+        if self.wild {
+            return true;
+        }
+
+        match &self.suit {
+            Some(own) if flags.smeared => smeared_suit(own) == smeared_suit(suit),
+            Some(own) => own == suit,
+            None => false,
+        }
+    }
+}
+
+fn best_flush(cards: &[PlayingCard], flags: HandFlags) -> Option<Vec<usize>> {
+    let needed = if flags.four_fingers { 4 } else { 5 };
+    let mut suits: Vec<&str> = vec!["Spades", "Hearts", "Clubs", "Diamonds"];
+
+    for card in cards {
+        if let Some(suit) = &card.suit
+            && !suits.contains(&suit.as_str())
+        {
+            suits.push(suit);
+        }
+    }
+
+    suits
+        .into_iter()
+        .map(|suit| {
+            (0..cards.len())
+                .filter(|&idx| cards[idx].has_suit(suit, flags))
+                .collect::<Vec<usize>>()
+        })
+        .filter(|members| members.len() >= needed)
+        .max_by_key(|members| members.len())
+}
+
+fn best_straight(cards: &[PlayingCard], flags: HandFlags) -> Option<Vec<usize>> {
+    let needed = if flags.four_fingers { 4 } else { 5 };
+    let max_gap = if flags.shortcut { 2 } else { 1 };
+
+    let mut ranks: Vec<u8> = cards.iter().filter_map(|card| card.rank).collect();
+    if ranks.contains(&14) {
+        ranks.push(1);
+    }
+    ranks.sort_unstable();
+    ranks.dedup();
+
+    let mut best: Option<(usize, usize)> = None;
+    let mut run_start = 0;
+
+    for idx in 0..ranks.len() {
+        if idx > 0 && ranks[idx] - ranks[idx - 1] > max_gap {
+            run_start = idx;
+        }
+
+        let len = idx - run_start + 1;
+        if len >= needed && best.is_none_or(|(start, end)| len > end - start) {
+            best = Some((run_start, idx));
+        }
+    }
+
+    let (start, end) = best?;
+    let in_straight: Vec<u8> = ranks[start..=end]
+        .iter()
+        .map(|&rank| if rank == 1 { 14 } else { rank })
+        .collect();
+
+    Some(
+        (0..cards.len())
+            .filter(|&idx| {
+                cards[idx]
+                    .rank
+                    .is_some_and(|rank| in_straight.contains(&rank))
+            })
+            .collect(),
+    )
+}
+
+pub(crate) fn evaluate_hands(cards: &[PlayingCard], flags: HandFlags) -> Vec<(String, Vec<usize>)> {
+    let stones: Vec<usize> = (0..cards.len()).filter(|&idx| cards[idx].stone).collect();
+
+    let mut by_rank: BTreeMap<u8, Vec<usize>> = BTreeMap::new();
+    for (idx, card) in cards.iter().enumerate() {
+        if let Some(rank) = card.rank {
+            by_rank.entry(rank).or_default().push(idx);
+        }
+    }
+
+    // Highest rank first, so the first group of a given size is the best one. A hand scores
+    // exactly `size` cards of it, so a Pair inside Three of a Kind is still two cards.
+    let groups: Vec<&Vec<usize>> = by_rank.values().rev().collect();
+    let position_of = |size: usize, skip: Option<usize>| {
+        (0..groups.len()).find(|&idx| Some(idx) != skip && groups[idx].len() >= size)
+    };
+    let group_of = |size: usize| position_of(size, None).map(|idx| groups[idx][..size].to_vec());
+
+    let flush = best_flush(cards, flags);
+    let straight = best_straight(cards, flags);
+    let five = group_of(5);
+    let four = group_of(4);
+    let three = group_of(3);
+    let pair = group_of(2);
+
+    let pairs: Vec<&[usize]> = groups
+        .iter()
+        .filter(|g| g.len() >= 2)
+        .map(|g| &g[..2])
+        .collect();
+    let two_pair = (pairs.len() >= 2).then(|| [pairs[0], pairs[1]].concat());
+
+    let full_house = position_of(3, None).and_then(|three| {
+        position_of(2, Some(three)).map(|pair| [&groups[three][..3], &groups[pair][..2]].concat())
+    });
+
+    let union = |a: &[usize], b: &[usize]| {
+        let mut merged: Vec<usize> = a.iter().chain(b).copied().collect();
+        merged.sort_unstable();
+        merged.dedup();
+        merged
+    };
+
+    // A hand of nothing but Stone cards is still a High Card; the stones are its scoring cards.
+    let high_card = match groups.first() {
+        Some(group) => Some(vec![group[0]]),
+        None => (!cards.is_empty()).then(Vec::new),
+    };
+
+    let mut found: HashMap<&str, Vec<usize>> = HashMap::new();
+    if let (Some(five), Some(flush)) = (&five, &flush) {
+        found.insert("Flush Five", union(five, flush));
+    }
+    if let (Some(full_house), Some(flush)) = (&full_house, &flush) {
+        found.insert("Flush House", union(full_house, flush));
+    }
+    if let Some(five) = five {
+        found.insert("Five of a Kind", five);
+    }
+    if let (Some(straight), Some(flush)) = (&straight, &flush) {
+        found.insert("Straight Flush", union(straight, flush));
+    }
+    if let Some(four) = four {
+        found.insert("Four of a Kind", four);
+    }
+    if let Some(full_house) = full_house {
+        found.insert("Full House", full_house);
+    }
+    if let Some(flush) = flush {
+        found.insert("Flush", flush);
+    }
+    if let Some(straight) = straight {
+        found.insert("Straight", straight);
+    }
+    if let Some(three) = three {
+        found.insert("Three of a Kind", three);
+    }
+    if let Some(two_pair) = two_pair {
+        found.insert("Two Pair", two_pair);
+    }
+    if let Some(pair) = pair {
+        found.insert("Pair", pair);
+    }
+    if let Some(high_card) = high_card {
+        found.insert("High Card", high_card);
+    }
+
+    // Stone cards have no rank or suit, but they always score.
+    HAND_ORDER
+        .iter()
+        .filter_map(|name| {
+            found
+                .remove(name)
+                .map(|scoring| (name.to_string(), union(&scoring, &stones)))
+        })
+        .collect()
+}
+
+fn hand_flags(opts: Option<&LuaTable>) -> LuaResult<HandFlags> {
+    let mut flags = HandFlags::default();
+
+    if let Some(opts) = opts {
+        flags.four_fingers = opts.get::<Option<bool>>("four_fingers")?.unwrap_or(false);
+        flags.shortcut = opts.get::<Option<bool>>("shortcut")?.unwrap_or(false);
+        flags.smeared = opts.get::<Option<bool>>("smeared")?.unwrap_or(false);
+    }
+
+    Ok(flags)
+}
+
+fn custom_hands(lua: &Lua) -> LuaResult<LuaTable> {
+    match lua.named_registry_value::<Option<LuaTable>>(CUSTOM_HANDS_KEY)? {
+        Some(tbl) => Ok(tbl),
+        None => {
+            let tbl = lua.create_table()?;
+            lua.set_named_registry_value(CUSTOM_HANDS_KEY, &tbl)?;
+            Ok(tbl)
+        }
+    }
+}
+
+fn builtin_priority(name: &str) -> Option<f64> {
+    HAND_ORDER
+        .iter()
+        .position(|hand| *hand == name)
+        .map(|pos| (HAND_ORDER.len() - pos) as f64)
+}
+
+// Built-in hands rank from 1 (High Card) to 12 (Flush Five). A custom hand registered without a
+// priority ranks above all of them, so it becomes `best` whenever its predicate matches.
+pub(crate) fn register_poker_hand(
+    lua: &Lua,
+    (name, predicate, priority): (String, LuaFunction, Option<f64>),
+) -> LuaResult<()> {
+    if builtin_priority(&name).is_some() {
+        return Err(mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: {} is a built-in poker hand and cannot be replaced",
+            name
+        )));
+    }
+
+    let entry = lua.create_table()?;
+    entry.set("predicate", predicate)?;
+    entry.set(
+        "priority",
+        priority.unwrap_or((HAND_ORDER.len() + 1) as f64),
+    )?;
+
+    custom_hands(lua)?.set(name, entry)
+}
+
+pub(crate) fn unregister_poker_hand(lua: &Lua, name: String) -> LuaResult<()> {
+    custom_hands(lua)?.set(name, LuaValue::Nil)
+}
+
+pub(crate) fn evaluate_poker_hand(
+    lua: &Lua,
+    (cards, opts): (LuaTable, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let flags = hand_flags(opts.as_ref())?;
+    let card_tbls: Vec<LuaTable> = cards
+        .sequence_values::<LuaTable>()
+        .collect::<LuaResult<_>>()?;
+    let parsed: Vec<PlayingCard> = card_tbls
+        .iter()
+        .map(PlayingCard::from_lua)
+        .collect::<LuaResult<_>>()?;
+
+    let mut ranked: Vec<(f64, String, LuaTable)> = Vec::new();
+
+    for (name, scoring) in evaluate_hands(&parsed, flags) {
+        let scoring_tbl =
+            lua.create_sequence_from(scoring.iter().map(|&idx| card_tbls[idx].clone()))?;
+        ranked.push((builtin_priority(&name).unwrap_or(0.0), name, scoring_tbl));
+    }
+
+    let flags_tbl = lua.create_table()?;
+    flags_tbl.set("four_fingers", flags.four_fingers)?;
+    flags_tbl.set("shortcut", flags.shortcut)?;
+    flags_tbl.set("smeared", flags.smeared)?;
+
+    for pair in custom_hands(lua)?.pairs::<String, LuaTable>() {
+        let (name, entry) = pair?;
+        let predicate: LuaFunction = entry.get("predicate")?;
+        let priority: f64 = entry.get("priority")?;
+
+        match predicate.call::<LuaValue>((cards.clone(), flags_tbl.clone()))? {
+            LuaValue::Table(scoring) => ranked.push((priority, name, scoring)),
+            LuaValue::Boolean(true) => ranked.push((priority, name, cards.clone())),
+            _ => {}
+        }
+    }
+
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+    let result = lua.create_table()?;
+    let hands = lua.create_table()?;
+    let order = lua.create_table()?;
+
+    for (_, name, scoring) in ranked {
+        order.push(name.clone())?;
+        hands.set(name, scoring)?;
+    }
+
+    result.set("best", order.get::<LuaValue>(1)?)?;
+    result.set("hands", hands)?;
+    result.set("order", order)?;
+
+    Ok(result)
+}
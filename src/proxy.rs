@@ -0,0 +1,321 @@
+use mlua::prelude::*;
+use std::cell::Cell;
+use std::rc::Rc;
+
+enum ProxyKind {
+    Frozen,
+    Observed(LuaFunction),
+}
+
+#[derive(Clone)]
+struct ProxyState {
+    kind: Rc<ProxyKind>,
+    deep: bool,
+}
+
+// Proxies are userdata rather than empty tables: LuaJIT ignores __pairs, __ipairs and __len on
+// tables, so a table proxy would quietly iterate as empty. `pairs` and `ipairs` on a proxy fail
+// loudly instead; iterate with `proxy_pairs`/`proxy_ipairs`, or unwrap it with `proxy_target`.
+struct Proxy {
+    target: LuaTable,
+    path: String,
+    state: ProxyState,
+    // Child table -> its proxy, weak on the keys.
+    cache: LuaTable,
+}
+
+fn key_text(key: &LuaValue) -> String {
+    match key {
+        LuaValue::String(s) => s.to_string_lossy(),
+        other => other
+            .to_string()
+            .unwrap_or_else(|_| format!("<{}>", other.type_name())),
+    }
+}
+
+fn join_path(path: &str, key: &LuaValue) -> String {
+    if path.is_empty() {
+        key_text(key)
+    } else {
+        format!("{}.{}", path, key_text(key))
+    }
+}
+
+impl Proxy {
+    // Nested tables are wrapped on first access and cached so repeated reads return the same
+    // proxy.
+    fn wrap_child(&self, lua: &Lua, key: &LuaValue, value: LuaValue) -> LuaResult<LuaValue> {
+        let LuaValue::Table(child) = value else {
+            return Ok(value);
+        };
+
+        if !self.state.deep {
+            return Ok(LuaValue::Table(child));
+        }
+
+        if let Some(proxy) = self
+            .cache
+            .raw_get::<Option<LuaAnyUserData>>(child.clone())?
+        {
+            return Ok(LuaValue::UserData(proxy));
+        }
+
+        let proxy = make_proxy(
+            lua,
+            child.clone(),
+            join_path(&self.path, key),
+            self.state.clone(),
+        )?;
+        self.cache.raw_set(child, proxy.clone())?;
+
+        Ok(LuaValue::UserData(proxy))
+    }
+
+    fn entries(&self, lua: &Lua) -> LuaResult<Vec<(LuaValue, LuaValue)>> {
+        let mut entries = Vec::new();
+        for pair in self.target.pairs::<LuaValue, LuaValue>() {
+            let (k, v) = pair?;
+            let v = self.wrap_child(lua, &k, v)?;
+            entries.push((k, v));
+        }
+
+        Ok(entries)
+    }
+
+    fn sequence(&self, lua: &Lua) -> LuaResult<Vec<(LuaValue, LuaValue)>> {
+        let mut entries = Vec::new();
+        for (idx, value) in self.target.sequence_values::<LuaValue>().enumerate() {
+            let key = LuaValue::Integer(idx as i64 + 1);
+            let value = self.wrap_child(lua, &key, value?)?;
+            entries.push((key, value));
+        }
+
+        Ok(entries)
+    }
+}
+
+impl LuaUserData for Proxy {
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_meta_method(LuaMetaMethod::Index, |lua, this, key: LuaValue| {
+            let value = this.target.get::<LuaValue>(key.clone())?;
+            this.wrap_child(lua, &key, value)
+        });
+        methods.add_meta_method(
+            LuaMetaMethod::NewIndex,
+            |_, this, (key, value): (LuaValue, LuaValue)| {
+                let at = join_path(&this.path, &key);
+
+                match this.state.kind.as_ref() {
+                    ProxyKind::Frozen => Err(mlua::Error::RuntimeError(format!(
+                        "[INSOLENCE] Error: attempt to modify frozen table at {}",
+                        at
+                    ))),
+                    ProxyKind::Observed(callback) => {
+                        let old = this.target.raw_get::<LuaValue>(key.clone())?;
+                        this.target.set(key, value.clone())?;
+                        callback.call::<()>((at, old, value))
+                    }
+                }
+            },
+        );
+        methods.add_meta_method(LuaMetaMethod::Len, |_, this, ()| Ok(this.target.raw_len()));
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            let label = if this.path.is_empty() {
+                "<root>"
+            } else {
+                this.path.as_str()
+            };
+            let kind = match this.state.kind.as_ref() {
+                ProxyKind::Frozen => "frozen",
+                ProxyKind::Observed(_) => "observed",
+            };
+            Ok(format!("{} proxy: {}", kind, label))
+        });
+    }
+}
+
+fn make_proxy(
+    lua: &Lua,
+    target: LuaTable,
+    path: String,
+    state: ProxyState,
+) -> LuaResult<LuaAnyUserData> {
+    let cache = lua.create_table()?;
+    let weak = lua.create_table()?;
+    weak.set("__mode", "k")?;
+    cache.set_metatable(Some(weak));
+
+    lua.create_userdata(Proxy {
+        target,
+        path,
+        state,
+        cache,
+    })
+}
+
+fn name_opt(opts: Option<&LuaTable>) -> LuaResult<String> {
+    match opts {
+        Some(opts) => Ok(opts.get::<Option<String>>("name")?.unwrap_or_default()),
+        None => Ok(String::new()),
+    }
+}
+
+pub(crate) fn freeze(
+    lua: &Lua,
+    (tbl, deep, opts): (LuaTable, Option<bool>, Option<LuaTable>),
+) -> LuaResult<LuaAnyUserData> {
+    let state = ProxyState {
+        kind: Rc::new(ProxyKind::Frozen),
+        deep: deep.unwrap_or(false),
+    };
+
+    make_proxy(lua, tbl, name_opt(opts.as_ref())?, state)
+}
+
+pub(crate) fn observe(
+    lua: &Lua,
+    (tbl, callback, opts): (LuaTable, LuaFunction, Option<LuaTable>),
+) -> LuaResult<LuaAnyUserData> {
+    let deep = match &opts {
+        Some(opts) => opts.get::<Option<bool>>("deep")?.unwrap_or(true),
+        None => true,
+    };
+    let state = ProxyState {
+        kind: Rc::new(ProxyKind::Observed(callback)),
+        deep,
+    };
+
+    make_proxy(lua, tbl, name_opt(opts.as_ref())?, state)
+}
+
+// The wrapped table behind a proxy; anything else is returned unchanged.
+pub(crate) fn proxy_target(_: &Lua, value: LuaValue) -> LuaResult<LuaValue> {
+    if let LuaValue::UserData(ud) = &value
+        && let Ok(proxy) = ud.borrow::<Proxy>()
+    {
+        return Ok(LuaValue::Table(proxy.target.clone()));
+    }
+
+    Ok(value)
+}
+
+fn iterator(lua: &Lua, entries: Vec<(LuaValue, LuaValue)>) -> LuaResult<LuaFunction> {
+    let position = Cell::new(0);
+
+    lua.create_function(move |_, _: LuaMultiValue| {
+        let idx = position.get();
+        position.set(idx + 1);
+
+        Ok(match entries.get(idx) {
+            Some((k, v)) => (k.clone(), v.clone()),
+            None => (LuaValue::Nil, LuaValue::Nil),
+        })
+    })
+}
+
+// `pairs` that sees through proxies (nested tables come back wrapped) and also takes plain tables.
+pub(crate) fn proxy_pairs(lua: &Lua, value: LuaValue) -> LuaResult<LuaFunction> {
+    let entries = match &value {
+        LuaValue::UserData(ud) if ud.is::<Proxy>() => ud.borrow::<Proxy>()?.entries(lua)?,
+        LuaValue::Table(tbl) => tbl.pairs().collect::<LuaResult<_>>()?,
+        other => return Err(not_iterable(other)),
+    };
+
+    iterator(lua, entries)
+}
+
+// `ipairs` counterpart of `proxy_pairs`.
+pub(crate) fn proxy_ipairs(lua: &Lua, value: LuaValue) -> LuaResult<LuaFunction> {
+    let entries = match &value {
+        LuaValue::UserData(ud) if ud.is::<Proxy>() => ud.borrow::<Proxy>()?.sequence(lua)?,
+        LuaValue::Table(tbl) => tbl
+            .sequence_values::<LuaValue>()
+            .enumerate()
+            .map(|(idx, v)| Ok((LuaValue::Integer(idx as i64 + 1), v?)))
+            .collect::<LuaResult<_>>()?,
+        other => return Err(not_iterable(other)),
+    };
+
+    iterator(lua, entries)
+}
+
+fn not_iterable(value: &LuaValue) -> mlua::Error {
+    mlua::Error::RuntimeError(format!(
+        "[INSOLENCE] Error: expected a table or proxy, got {}",
+        value.type_name()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lua_with_proxies() -> Lua {
+        let lua = Lua::new();
+        let globals = lua.globals();
+        globals
+            .set("freeze", lua.create_function(freeze).unwrap())
+            .unwrap();
+        globals
+            .set("observe", lua.create_function(observe).unwrap())
+            .unwrap();
+        globals
+            .set("proxy_pairs", lua.create_function(proxy_pairs).unwrap())
+            .unwrap();
+        globals
+            .set("proxy_ipairs", lua.create_function(proxy_ipairs).unwrap())
+            .unwrap();
+        lua
+    }
+
+    #[test]
+    fn frozen_tables_iterate_through_the_helpers() {
+        let lua = lua_with_proxies();
+
+        lua.load(
+            r#"
+            local frozen = freeze({10, 20, name = "joker", pos = {x = 1}}, true)
+            assert(#frozen == 2)
+
+            local seen = {}
+            for k, v in proxy_pairs(frozen) do
+                seen[k] = v
+            end
+            assert(seen[1] == 10 and seen[2] == 20 and seen.name == "joker")
+            assert(not pcall(function() seen.pos.x = 2 end))
+
+            local sum = 0
+            for _, v in proxy_ipairs(frozen) do
+                sum = sum + v
+            end
+            assert(sum == 30)
+
+            assert(not pcall(pairs, frozen), "pairs must not silently see an empty table")
+            assert(not pcall(function() frozen.name = "other" end))
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn observed_writes_reach_the_callback() {
+        let lua = lua_with_proxies();
+
+        lua.load(
+            r#"
+            local target = {pos = {x = 1}}
+            local log = {}
+            local observed = observe(target, function(path, old, new)
+                log[#log + 1] = path .. ":" .. tostring(old) .. "->" .. tostring(new)
+            end)
+
+            observed.pos.x = 2
+            assert(target.pos.x == 2)
+            assert(log[1] == "pos.x:1->2")
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+}
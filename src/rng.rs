@@ -1,3 +1,6 @@
+use crate::array::{Holes, read_array};
+use crate::set::{Bag, to_bag};
+use crate::tbl::seq_index;
 use mlua::prelude::*;
 use rand::prelude::*;
 
@@ -207,10 +210,30 @@ pub(crate) fn chance(_: &Lua, percent_chance: f32) -> LuaResult<bool> {
     Ok(percent_chance <= rand::rng().random_range(0.0..=100.0))
 }
 
+// A single key, an array of keys, a map of key = true (like G.GAME.banned_keys) or a Set.
+fn banned_keys(lua: &Lua, banned: LuaValue) -> LuaResult<Bag> {
+    let keys = match banned {
+        LuaValue::String(key) => vec![LuaValue::String(key)],
+        LuaValue::Table(tbl) => {
+            let mut keys = read_array(&tbl, Holes::Skip)?;
+            for pair in tbl.pairs::<LuaValue, LuaValue>() {
+                let (k, v) = pair?;
+                if seq_index(&k).is_none() && lua.unpack::<bool>(v)? {
+                    keys.push(k);
+                }
+            }
+            keys
+        }
+        other => return to_bag(&other, true),
+    };
+
+    to_bag(&LuaValue::Table(lua.create_sequence_from(keys)?), true)
+}
+
 type RandJokerParams = (
     Option<String>,
     Option<LuaTable>,
-    Option<String>,
+    Option<LuaValue>,
     Option<LuaTable>,
     Option<bool>,
 );
@@ -225,6 +248,8 @@ pub(crate) fn random_joker(
             .unwrap()
     });
 
+    let banned = banned_keys(lua, banned_card.unwrap_or(LuaValue::Nil))?;
+
     let mut selection = lua.create_table()?;
     selection.set("key", "n/a")?;
 
@@ -262,9 +287,7 @@ pub(crate) fn random_joker(
         let discovered: bool = selection.get("discovered").unwrap_or(false);
         if discovered || !no_undiscovered.unwrap_or(false) {
             // Check banned card
-            if banned_card.is_none()
-                || (banned_card.is_some() && banned_card.as_ref().unwrap() != &key)
-            {
+            if !banned.contains_str(&key) {
                 passes += 1;
             }
         }
@@ -280,3 +303,26 @@ pub(crate) fn random_joker(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn banned_keys_accepts_every_shape() {
+        let lua = Lua::new();
+        let shapes = [
+            "return 'j_joker'",
+            "return {'j_joker'}",
+            "return {j_joker = true, j_blueprint = false}",
+        ];
+
+        for shape in shapes {
+            let value: LuaValue = lua.load(shape).eval().unwrap();
+            let banned = banned_keys(&lua, value).unwrap();
+
+            assert!(banned.contains_str("j_joker"), "{}", shape);
+            assert!(!banned.contains_str("j_blueprint"), "{}", shape);
+        }
+    }
+}
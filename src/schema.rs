@@ -0,0 +1,317 @@
+use crate::tbl::{DeepCopier, MetatableMode, as_number};
+use mlua::prelude::*;
+
+const TYPE_NAMES: [&str; 9] = [
+    "any", "nil", "boolean", "number", "integer", "string", "table", "function", "userdata",
+];
+
+struct Validator<'a> {
+    lua: &'a Lua,
+    fill_defaults: bool,
+    errors: Vec<(String, String)>,
+}
+
+fn schema_error(path: &str, msg: &str) -> mlua::Error {
+    mlua::Error::RuntimeError(format!(
+        "[INSOLENCE] Error: invalid schema at {}: {}",
+        path, msg
+    ))
+}
+
+fn value_type(value: &LuaValue) -> &'static str {
+    match value {
+        LuaValue::Integer(_) | LuaValue::Number(_) => "number",
+        LuaValue::UserData(_) | LuaValue::LightUserData(_) => "userdata",
+        other => other.type_name(),
+    }
+}
+
+fn matches_type(value: &LuaValue, expected: &str) -> bool {
+    match expected {
+        "any" => true,
+        "integer" => as_number(value).is_some_and(|n| n.fract() == 0.0),
+        other => value_type(value) == other,
+    }
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn describe(value: &LuaValue) -> String {
+    match value {
+        LuaValue::String(s) => format!("\"{}\"", s.to_string_lossy()),
+        LuaValue::Integer(_) | LuaValue::Number(_) | LuaValue::Boolean(_) => {
+            value.to_string().unwrap_or_default()
+        }
+        other => other.type_name().to_string(),
+    }
+}
+
+impl Validator<'_> {
+    fn fail(&mut self, path: &str, msg: String) {
+        let at = if path.is_empty() { "<root>" } else { path };
+        self.errors.push((at.to_string(), msg));
+    }
+
+    fn allowed_types(schema: &LuaTable, path: &str) -> LuaResult<Vec<String>> {
+        let types = match schema.get::<LuaValue>("type")? {
+            LuaValue::Nil => vec!["any".to_string()],
+            LuaValue::String(name) => vec![name.to_str()?.to_string()],
+            LuaValue::Table(names) => names
+                .sequence_values::<String>()
+                .collect::<LuaResult<_>>()?,
+            other => {
+                return Err(schema_error(
+                    path,
+                    &format!("type must be a string or list, got {}", other.type_name()),
+                ));
+            }
+        };
+
+        if let Some(bad) = types
+            .iter()
+            .find(|name| !TYPE_NAMES.contains(&name.as_str()))
+        {
+            return Err(schema_error(path, &format!("unknown type {}", bad)));
+        }
+
+        Ok(types)
+    }
+
+    fn check(&mut self, value: &LuaValue, schema: &LuaTable, path: &str) -> LuaResult<()> {
+        let types = Self::allowed_types(schema, path)?;
+
+        if !types.iter().any(|expected| matches_type(value, expected)) {
+            self.fail(
+                path,
+                format!("expected {}, got {}", types.join(" or "), describe(value)),
+            );
+            return Ok(());
+        }
+
+        if let Some(options) = schema.get::<Option<LuaTable>>("enum")? {
+            let mut allowed = Vec::new();
+            for option in options.sequence_values::<LuaValue>() {
+                allowed.push(option?);
+            }
+
+            if !allowed.contains(value) {
+                let listed: Vec<String> = allowed.iter().map(describe).collect();
+                self.fail(
+                    path,
+                    format!("{} is not one of {}", describe(value), listed.join(", ")),
+                );
+            }
+        }
+
+        if let Some(n) = as_number(value) {
+            if let Some(min) = schema.get::<Option<f64>>("min")?
+                && n < min
+            {
+                self.fail(path, format!("{} is below the minimum of {}", n, min));
+            }
+            if let Some(max) = schema.get::<Option<f64>>("max")?
+                && n > max
+            {
+                self.fail(path, format!("{} is above the maximum of {}", n, max));
+            }
+        }
+
+        let LuaValue::Table(tbl) = value else {
+            return Ok(());
+        };
+
+        if let Some(fields) = schema.get::<Option<LuaTable>>("fields")? {
+            for pair in fields.pairs::<String, LuaTable>() {
+                let (name, field_schema) = pair?;
+                let field_path = join_path(path, &name);
+                let field_value = tbl.raw_get::<LuaValue>(name.as_str())?;
+
+                if !field_value.is_nil() {
+                    self.check(&field_value, &field_schema, &field_path)?;
+                    continue;
+                }
+
+                let default = field_schema.get::<LuaValue>("default")?;
+                if !default.is_nil() {
+                    if self.fill_defaults {
+                        // A fresh copier per default, so every filled-in table is its own copy.
+                        let filled =
+                            DeepCopier::new(self.lua, MetatableMode::Keep).copy_value(default)?;
+                        tbl.raw_set(name.as_str(), filled.clone())?;
+                        self.check(&filled, &field_schema, &field_path)?;
+                    }
+                } else if field_schema
+                    .get::<Option<bool>>("required")?
+                    .unwrap_or(false)
+                {
+                    self.fail(&field_path, "missing required field".into());
+                }
+            }
+
+            if schema.get::<Option<bool>>("extra")? == Some(false) {
+                for pair in tbl.pairs::<LuaValue, LuaValue>() {
+                    let (key, _) = pair?;
+                    let known = match &key {
+                        LuaValue::String(name) => fields.contains_key(name.clone())?,
+                        _ => false,
+                    };
+
+                    if !known {
+                        let key_text = key.to_string().unwrap_or_else(|_| describe(&key));
+                        self.fail(&join_path(path, &key_text), "unexpected field".into());
+                    }
+                }
+            }
+        }
+
+        if let Some(items) = schema.get::<Option<LuaTable>>("items")? {
+            for idx in 1..=tbl.raw_len() {
+                let item = tbl.raw_get::<LuaValue>(idx)?;
+                self.check(&item, &items, &join_path(path, &idx.to_string()))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn run_validation(
+    lua: &Lua,
+    value: &LuaValue,
+    schema: &LuaTable,
+    opts: Option<&LuaTable>,
+    fill_by_default: bool,
+) -> LuaResult<Vec<(String, String)>> {
+    let mut name = String::new();
+    let mut fill_defaults = fill_by_default;
+
+    if let Some(opts) = opts {
+        name = opts.get::<Option<String>>("name")?.unwrap_or_default();
+        fill_defaults = opts
+            .get::<Option<bool>>("fill_defaults")?
+            .unwrap_or(fill_by_default);
+    }
+
+    let mut validator = Validator {
+        lua,
+        fill_defaults,
+        errors: Vec::new(),
+    };
+    validator.check(value, schema, &name)?;
+
+    Ok(validator.errors)
+}
+
+// Only reports problems; defaults are filled in when `fill_defaults = true` is passed.
+pub(crate) fn validate(
+    lua: &Lua,
+    (value, schema, opts): (LuaValue, LuaTable, Option<LuaTable>),
+) -> LuaResult<(bool, LuaTable)> {
+    let errors = run_validation(lua, &value, &schema, opts.as_ref(), false)?;
+    let error_tbl = lua.create_table()?;
+
+    for (path, message) in &errors {
+        let entry = lua.create_table()?;
+        entry.set("path", path.as_str())?;
+        entry.set("message", message.as_str())?;
+        error_tbl.raw_push(entry)?;
+    }
+
+    Ok((errors.is_empty(), error_tbl))
+}
+
+// Fills missing fields from their defaults in place (unless `fill_defaults = false`) and returns
+// the value, so a config can be checked and completed in one call.
+pub(crate) fn assert_schema(
+    lua: &Lua,
+    (value, schema, opts): (LuaValue, LuaTable, Option<LuaTable>),
+) -> LuaResult<LuaValue> {
+    let errors = run_validation(lua, &value, &schema, opts.as_ref(), true)?;
+
+    if errors.is_empty() {
+        return Ok(value);
+    }
+
+    let lines: Vec<String> = errors
+        .iter()
+        .map(|(path, message)| format!("  {}: {}", path, message))
+        .collect();
+
+    Err(mlua::Error::RuntimeError(format!(
+        "[INSOLENCE] Error: validation failed:\n{}",
+        lines.join("\n")
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shared_default_schema(lua: &Lua) -> LuaTable {
+        lua.load(
+            r#"
+            local shared = {enabled = true}
+            return {type = "table", fields = {
+                first = {type = "table", default = shared},
+                second = {type = "table", default = shared},
+            }}
+            "#,
+        )
+        .eval()
+        .unwrap()
+    }
+
+    #[test]
+    fn validate_leaves_the_value_alone() {
+        let lua = Lua::new();
+        let config = lua.create_table().unwrap();
+
+        let (ok, _) = validate(
+            &lua,
+            (
+                LuaValue::Table(config.clone()),
+                shared_default_schema(&lua),
+                None,
+            ),
+        )
+        .unwrap();
+
+        assert!(ok);
+        assert!(config.raw_get::<LuaValue>("first").unwrap().is_nil());
+        assert!(config.raw_get::<LuaValue>("second").unwrap().is_nil());
+    }
+
+    #[test]
+    fn shared_defaults_are_filled_as_separate_copies() {
+        let lua = Lua::new();
+        let schema = shared_default_schema(&lua);
+        let config = lua.create_table().unwrap();
+
+        assert_schema(
+            &lua,
+            (LuaValue::Table(config.clone()), schema.clone(), None),
+        )
+        .unwrap();
+
+        let first: LuaTable = config.raw_get("first").unwrap();
+        let second: LuaTable = config.raw_get("second").unwrap();
+        let shared: LuaTable = schema
+            .get::<LuaTable>("fields")
+            .unwrap()
+            .get::<LuaTable>("first")
+            .unwrap()
+            .get("default")
+            .unwrap();
+
+        assert_ne!(first.to_pointer(), second.to_pointer());
+        assert_ne!(first.to_pointer(), shared.to_pointer());
+        assert!(first.get::<bool>("enabled").unwrap());
+        assert!(second.get::<bool>("enabled").unwrap());
+    }
+}
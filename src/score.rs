@@ -0,0 +1,523 @@
+use crate::poker::{PlayingCard, rank_from_value};
+use crate::tbl::{as_number, lookup_path, parse_path};
+use mlua::prelude::*;
+
+#[derive(Clone, Debug)]
+pub(crate) struct ScoreCard {
+    pub(crate) rank: Option<u8>,
+    pub(crate) suit: Option<String>,
+    pub(crate) chips: f64,
+    pub(crate) wild: bool,
+    pub(crate) stone: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum EffectKind {
+    Chips,
+    Mult,
+    XMult,
+    Retrigger,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct JokerEffect {
+    pub(crate) name: String,
+    pub(crate) kind: EffectKind,
+    pub(crate) amount: f64,
+    pub(crate) suits: Vec<String>,
+    pub(crate) ranks: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ScoreStep {
+    pub(crate) source: String,
+    pub(crate) action: &'static str,
+    pub(crate) amount: f64,
+    pub(crate) chips: f64,
+    pub(crate) mult: f64,
+}
+
+pub(crate) fn rank_chips(rank: u8) -> f64 {
+    match rank {
+        14 => 11.0,
+        11..=13 => 10.0,
+        other => other as f64,
+    }
+}
+
+impl JokerEffect {
+    // Effects with no suit or rank condition act once on the whole hand, like a joker's main effect.
+    fn is_per_card(&self) -> bool {
+        !self.suits.is_empty() || !self.ranks.is_empty()
+    }
+
+    fn matches(&self, card: &ScoreCard) -> bool {
+        if card.stone {
+            return !self.is_per_card();
+        }
+
+        let suit_ok = self.suits.is_empty()
+            || card.wild
+            || card
+                .suit
+                .as_ref()
+                .is_some_and(|suit| self.suits.contains(suit));
+        let rank_ok =
+            self.ranks.is_empty() || card.rank.is_some_and(|rank| self.ranks.contains(&rank));
+
+        suit_ok && rank_ok
+    }
+
+    fn action(&self) -> &'static str {
+        match self.kind {
+            EffectKind::Chips => "chips",
+            EffectKind::Mult => "mult",
+            EffectKind::XMult => "xmult",
+            EffectKind::Retrigger => "retrigger",
+        }
+    }
+}
+
+fn apply(kind: EffectKind, amount: f64, chips: &mut f64, mult: &mut f64) {
+    match kind {
+        EffectKind::Chips => *chips += amount,
+        EffectKind::Mult => *mult += amount,
+        EffectKind::XMult => *mult *= amount,
+        EffectKind::Retrigger => {}
+    }
+}
+
+// `scale` multiplies every chip and mult amount the way `mod_vals` does; retrigger counts stay whole.
+pub(crate) fn simulate(
+    base_chips: f64,
+    base_mult: f64,
+    cards: &[ScoreCard],
+    effects: &[JokerEffect],
+    scale: f64,
+) -> (f64, f64, f64, Vec<ScoreStep>) {
+    let mut chips = base_chips;
+    let mut mult = base_mult;
+    let mut trace = vec![ScoreStep {
+        source: "hand".into(),
+        action: "base",
+        amount: 0.0,
+        chips,
+        mult,
+    }];
+
+    let amount_of = |effect: &JokerEffect| match effect.kind {
+        EffectKind::Retrigger => effect.amount,
+        _ => effect.amount * scale,
+    };
+
+    for (idx, card) in cards.iter().enumerate() {
+        let retriggers: f64 = effects
+            .iter()
+            .filter(|effect| effect.kind == EffectKind::Retrigger && effect.matches(card))
+            .map(|effect| effect.amount.max(0.0).floor())
+            .sum();
+
+        for trigger in 0..=(retriggers as usize) {
+            let source = if trigger == 0 {
+                format!("card {}", idx + 1)
+            } else {
+                format!("card {} (retrigger {})", idx + 1, trigger)
+            };
+
+            chips += card.chips;
+            trace.push(ScoreStep {
+                source: source.clone(),
+                action: "chips",
+                amount: card.chips,
+                chips,
+                mult,
+            });
+
+            for effect in effects.iter().filter(|effect| {
+                effect.kind != EffectKind::Retrigger && effect.is_per_card() && effect.matches(card)
+            }) {
+                let amount = amount_of(effect);
+                apply(effect.kind, amount, &mut chips, &mut mult);
+                trace.push(ScoreStep {
+                    source: format!("{} on {}", effect.name, source),
+                    action: effect.action(),
+                    amount,
+                    chips,
+                    mult,
+                });
+            }
+        }
+    }
+
+    for effect in effects
+        .iter()
+        .filter(|effect| effect.kind != EffectKind::Retrigger && !effect.is_per_card())
+    {
+        let amount = amount_of(effect);
+        apply(effect.kind, amount, &mut chips, &mut mult);
+        trace.push(ScoreStep {
+            source: effect.name.clone(),
+            action: effect.action(),
+            amount,
+            chips,
+            mult,
+        });
+    }
+
+    ((chips * mult).floor(), chips, mult, trace)
+}
+
+fn ranks_arg(value: LuaValue) -> LuaResult<Vec<u8>> {
+    let one = |value: &LuaValue| -> LuaResult<u8> {
+        let rank = match value {
+            LuaValue::String(s) => rank_from_value(&s.to_str()?),
+            other => as_number(other).map(|n| n as u8),
+        };
+
+        rank.ok_or_else(|| {
+            mlua::Error::RuntimeError(format!(
+                "[INSOLENCE] Error: {} is not a card rank",
+                value.to_string().unwrap_or_default()
+            ))
+        })
+    };
+
+    match value {
+        LuaValue::Nil => Ok(Vec::new()),
+        LuaValue::Table(tbl) => tbl
+            .sequence_values::<LuaValue>()
+            .map(|rank| one(&rank?))
+            .collect(),
+        other => Ok(vec![one(&other)?]),
+    }
+}
+
+fn suits_arg(value: LuaValue) -> LuaResult<Vec<String>> {
+    match value {
+        LuaValue::Nil => Ok(Vec::new()),
+        LuaValue::Table(tbl) => tbl.sequence_values::<String>().collect(),
+        LuaValue::String(s) => Ok(vec![s.to_str()?.to_string()]),
+        other => Err(mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: expected a suit or list of suits, got {}",
+            other.type_name()
+        ))),
+    }
+}
+
+impl ScoreCard {
+    fn from_lua(card: &LuaTable) -> LuaResult<Self> {
+        if card.contains_key("base")? {
+            let parsed = PlayingCard::from_lua(card)?;
+            let number_at = |path: &str| -> LuaResult<Option<f64>> {
+                let value = lookup_path(LuaValue::Table(card.clone()), &parse_path(path))?;
+                Ok(as_number(&value))
+            };
+
+            // Enhancement chips come from the card's ability, falling back to its center's config
+            // (m_stone is `{ bonus = 50 }`) for cards whose ability hasn't been filled in.
+            let bonus = match number_at("ability.bonus")? {
+                Some(bonus) => bonus,
+                None => number_at("config.center.config.bonus")?.unwrap_or(if parsed.stone {
+                    50.0
+                } else {
+                    0.0
+                }),
+            };
+            let perma_bonus = number_at("ability.perma_bonus")?.unwrap_or(0.0);
+            let nominal = card
+                .get::<LuaTable>("base")?
+                .get::<Option<f64>>("nominal")?;
+
+            let chips = if parsed.stone {
+                bonus + perma_bonus
+            } else {
+                nominal.unwrap_or_else(|| parsed.rank.map(rank_chips).unwrap_or(0.0))
+                    + bonus
+                    + perma_bonus
+            };
+
+            return Ok(ScoreCard {
+                rank: parsed.rank,
+                suit: parsed.suit,
+                chips,
+                wild: parsed.wild,
+                stone: parsed.stone,
+            });
+        }
+
+        let rank = ranks_arg(card.get::<LuaValue>("rank")?)?.first().copied();
+        let chips = match card.get::<Option<f64>>("chips")? {
+            Some(chips) => chips,
+            None => rank.map(rank_chips).unwrap_or(0.0),
+        };
+
+        Ok(ScoreCard {
+            rank,
+            suit: card.get::<Option<String>>("suit")?,
+            chips,
+            wild: card.get::<Option<bool>>("wild")?.unwrap_or(false),
+            stone: card.get::<Option<bool>>("stone")?.unwrap_or(false),
+        })
+    }
+}
+
+impl JokerEffect {
+    fn from_lua(idx: usize, effect: &LuaTable) -> LuaResult<Self> {
+        let kind = match effect.get::<String>("type")?.as_str() {
+            "chips" => EffectKind::Chips,
+            "mult" => EffectKind::Mult,
+            "xmult" => EffectKind::XMult,
+            "retrigger" => EffectKind::Retrigger,
+            other => {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "[INSOLENCE] Error: unknown joker effect type {}",
+                    other
+                )));
+            }
+        };
+
+        Ok(JokerEffect {
+            name: effect
+                .get::<Option<String>>("name")?
+                .unwrap_or_else(|| format!("joker {}", idx + 1)),
+            kind,
+            amount: effect.get::<Option<f64>>("amount")?.unwrap_or(match kind {
+                EffectKind::XMult | EffectKind::Retrigger => 1.0,
+                _ => 0.0,
+            }),
+            suits: suits_arg(effect.get::<LuaValue>("suit")?)?,
+            ranks: ranks_arg(effect.get::<LuaValue>("rank")?)?,
+        })
+    }
+}
+
+pub(crate) fn simulate_score(
+    lua: &Lua,
+    (hand, cards, jokers, opts): (LuaTable, LuaTable, Option<LuaTable>, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let base_chips = hand.get::<Option<f64>>("chips")?.unwrap_or(0.0);
+    let base_mult = hand.get::<Option<f64>>("mult")?.unwrap_or(1.0);
+    let scale = match &opts {
+        Some(opts) => opts.get::<Option<f64>>("scale")?.unwrap_or(1.0),
+        None => 1.0,
+    };
+
+    let cards: Vec<ScoreCard> = cards
+        .sequence_values::<LuaTable>()
+        .map(|card| ScoreCard::from_lua(&card?))
+        .collect::<LuaResult<_>>()?;
+
+    let mut effects = Vec::new();
+    if let Some(jokers) = jokers {
+        for (idx, effect) in jokers.sequence_values::<LuaTable>().enumerate() {
+            effects.push(JokerEffect::from_lua(idx, &effect?)?);
+        }
+    }
+
+    let (score, chips, mult, steps) = simulate(base_chips, base_mult, &cards, &effects, scale);
+
+    let trace = lua.create_table()?;
+    for step in steps {
+        let entry = lua.create_table()?;
+        entry.set("source", step.source)?;
+        entry.set("action", step.action)?;
+        entry.set("amount", step.amount)?;
+        entry.set("chips", step.chips)?;
+        entry.set("mult", step.mult)?;
+        trace.push(entry)?;
+    }
+
+    let result = lua.create_table()?;
+    result.set("score", score)?;
+    result.set("chips", chips)?;
+    result.set("mult", mult)?;
+    result.set("trace", trace)?;
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(rank: u8, suit: &str) -> ScoreCard {
+        ScoreCard {
+            rank: Some(rank),
+            suit: Some(suit.to_string()),
+            chips: rank_chips(rank),
+            wild: false,
+            stone: false,
+        }
+    }
+
+    fn effect(kind: EffectKind, amount: f64) -> JokerEffect {
+        JokerEffect {
+            name: "joker".into(),
+            kind,
+            amount,
+            suits: Vec::new(),
+            ranks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn adds_card_chips_to_the_hand_base() {
+        let cards = [card(14, "Spades"), card(14, "Hearts")];
+        let (score, chips, mult, _) = simulate(10.0, 2.0, &cards, &[], 1.0);
+
+        assert_eq!(chips, 32.0);
+        assert_eq!(mult, 2.0);
+        assert_eq!(score, 64.0);
+    }
+
+    #[test]
+    fn applies_mult_before_xmult_in_joker_order() {
+        let effects = [
+            effect(EffectKind::Mult, 4.0),
+            effect(EffectKind::XMult, 3.0),
+        ];
+        let (score, _, mult, _) = simulate(10.0, 1.0, &[card(2, "Clubs")], &effects, 1.0);
+
+        assert_eq!(mult, 15.0);
+        assert_eq!(score, 180.0);
+    }
+
+    #[test]
+    fn retriggers_score_the_card_again() {
+        let effects = [effect(EffectKind::Retrigger, 2.0)];
+        let (_, chips, _, trace) = simulate(0.0, 1.0, &[card(10, "Hearts")], &effects, 1.0);
+
+        assert_eq!(chips, 30.0);
+        assert_eq!(trace[3].source, "card 1 (retrigger 2)");
+    }
+
+    #[test]
+    fn conditional_effects_only_hit_matching_cards() {
+        let hearts = JokerEffect {
+            suits: vec!["Hearts".into()],
+            ..effect(EffectKind::Mult, 3.0)
+        };
+        let aces = JokerEffect {
+            ranks: vec![14],
+            ..effect(EffectKind::Chips, 20.0)
+        };
+        let mut wild = card(5, "Clubs");
+        wild.wild = true;
+        let cards = [card(14, "Hearts"), card(9, "Spades"), wild];
+
+        let (_, chips, mult, _) = simulate(0.0, 1.0, &cards, &[hearts, aces], 1.0);
+
+        assert_eq!(chips, 11.0 + 20.0 + 9.0 + 5.0);
+        assert_eq!(mult, 1.0 + 3.0 + 3.0);
+    }
+
+    #[test]
+    fn stone_cards_ignore_suit_and_rank_conditions() {
+        let stone = ScoreCard {
+            rank: None,
+            suit: None,
+            chips: 50.0,
+            wild: false,
+            stone: true,
+        };
+        let hearts = JokerEffect {
+            suits: vec!["Hearts".into()],
+            ..effect(EffectKind::Mult, 3.0)
+        };
+
+        let (_, chips, mult, _) = simulate(0.0, 1.0, &[stone], &[hearts], 1.0);
+
+        assert_eq!((chips, mult), (50.0, 1.0));
+    }
+
+    #[test]
+    fn scale_leaves_retrigger_counts_alone() {
+        let effects = [
+            effect(EffectKind::Retrigger, 1.0),
+            effect(EffectKind::Chips, 5.0),
+        ];
+        let (_, chips, _, _) = simulate(0.0, 1.0, &[card(2, "Clubs")], &effects, 2.0);
+
+        assert_eq!(chips, 2.0 * 2.0 + 10.0);
+    }
+
+    #[test]
+    fn trace_records_running_totals() {
+        let effects = [effect(EffectKind::Mult, 4.0)];
+        let (_, _, _, trace) = simulate(5.0, 1.0, &[card(3, "Clubs")], &effects, 1.0);
+
+        let steps: Vec<(&str, &str, f64, f64, f64)> = trace
+            .iter()
+            .map(|step| {
+                (
+                    step.source.as_str(),
+                    step.action,
+                    step.amount,
+                    step.chips,
+                    step.mult,
+                )
+            })
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                ("hand", "base", 0.0, 5.0, 1.0),
+                ("card 1", "chips", 3.0, 8.0, 1.0),
+                ("joker", "mult", 4.0, 8.0, 5.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn stone_card_chips_come_from_its_ability() {
+        let lua = Lua::new();
+        let stone = lua
+            .load(
+                r#"{
+                    base = { id = 5, suit = "Hearts", nominal = 5 },
+                    config = { center = { key = "m_stone" } },
+                    ability = { name = "Stone Card", bonus = 50, perma_bonus = 10 },
+                }"#,
+            )
+            .eval::<LuaTable>()
+            .unwrap();
+
+        assert_eq!(ScoreCard::from_lua(&stone).unwrap().chips, 60.0);
+
+        stone.set("ability", LuaValue::Nil).unwrap();
+        assert_eq!(ScoreCard::from_lua(&stone).unwrap().chips, 50.0);
+    }
+
+    #[test]
+    fn missing_ability_bonus_falls_back_to_the_center() {
+        let lua = Lua::new();
+        let cards = lua
+            .load(
+                r#"{
+                    {
+                        base = { id = 5, suit = "Hearts", nominal = 5 },
+                        config = { center = { key = "m_stone", config = { bonus = 50 } } },
+                        ability = { name = "Stone Card" },
+                    },
+                    {
+                        base = { id = 5, suit = "Hearts", nominal = 5 },
+                        config = { center = { key = "m_stone" } },
+                        ability = { name = "Stone Card", perma_bonus = 5 },
+                    },
+                    {
+                        base = { id = 5, suit = "Hearts", nominal = 5 },
+                        config = { center = { key = "m_bonus", config = { bonus = 30 } } },
+                        ability = { name = "Bonus" },
+                    },
+                }"#,
+            )
+            .eval::<Vec<LuaTable>>()
+            .unwrap();
+
+        let chips: Vec<f64> = cards
+            .iter()
+            .map(|card| ScoreCard::from_lua(card).unwrap().chips)
+            .collect();
+        assert_eq!(chips, [50.0, 55.0, 35.0]);
+    }
+}
@@ -0,0 +1,1056 @@
+use crate::tbl::{compare_values, is_sequence, ordered_entries};
+use mlua::prelude::*;
+use std::ffi::c_void;
+
+const MAX_PARSE_DEPTH: usize = 512;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Unsupported {
+    Skip,
+    Placeholder,
+    Error,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum LiteralStyle {
+    Readable,
+    StrPack,
+}
+
+struct WriteOpts {
+    indent: Option<usize>,
+    sort_keys: bool,
+    arrays_as_objects: bool,
+    empty_as_array: bool,
+    unsupported: Unsupported,
+    style: LiteralStyle,
+    with_return: bool,
+}
+
+impl WriteOpts {
+    fn defaults() -> Self {
+        WriteOpts {
+            indent: None,
+            sort_keys: false,
+            arrays_as_objects: false,
+            empty_as_array: false,
+            unsupported: Unsupported::Error,
+            style: LiteralStyle::Readable,
+            with_return: false,
+        }
+    }
+
+    fn from_lua(opts: Option<&LuaTable>) -> LuaResult<Self> {
+        let mut write_opts = WriteOpts::defaults();
+
+        let Some(opts) = opts else {
+            return Ok(write_opts);
+        };
+
+        write_opts.indent = match opts.get::<LuaValue>("pretty")? {
+            LuaValue::Boolean(true) => Some(2),
+            LuaValue::Integer(n) => Some(n.max(0) as usize),
+            LuaValue::Number(n) => Some(n.max(0.0) as usize),
+            _ => None,
+        };
+        write_opts.sort_keys = opts.get::<Option<bool>>("sort_keys")?.unwrap_or(false);
+        write_opts.arrays_as_objects = match opts.get::<Option<String>>("arrays")?.as_deref() {
+            None | Some("auto") => false,
+            Some("object") => true,
+            Some(other) => return Err(bad_option("arrays", other)),
+        };
+        write_opts.empty_as_array = match opts.get::<Option<String>>("empty")?.as_deref() {
+            None | Some("object") => false,
+            Some("array") => true,
+            Some(other) => return Err(bad_option("empty", other)),
+        };
+        write_opts.unsupported = match opts.get::<Option<String>>("unsupported")?.as_deref() {
+            None | Some("error") => Unsupported::Error,
+            Some("skip") => Unsupported::Skip,
+            Some("placeholder") => Unsupported::Placeholder,
+            Some(other) => return Err(bad_option("unsupported", other)),
+        };
+        write_opts.style = match opts.get::<Option<String>>("style")?.as_deref() {
+            None | Some("readable") => LiteralStyle::Readable,
+            Some("str_pack") => LiteralStyle::StrPack,
+            Some(other) => return Err(bad_option("style", other)),
+        };
+        write_opts.with_return = opts
+            .get::<Option<bool>>("with_return")?
+            .unwrap_or(write_opts.style == LiteralStyle::StrPack);
+
+        Ok(write_opts)
+    }
+}
+
+fn bad_option(name: &str, value: &str) -> mlua::Error {
+    mlua::Error::RuntimeError(format!(
+        "[INSOLENCE] Error: unknown value {} for option {}",
+        value, name
+    ))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Format {
+    Json,
+    Lua,
+}
+
+struct Writer {
+    format: Format,
+    opts: WriteOpts,
+    out: String,
+    stack: Vec<*const c_void>,
+    path: Vec<String>,
+}
+
+pub(crate) fn is_identifier(key: &str) -> bool {
+    const KEYWORDS: [&str; 21] = [
+        "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if",
+        "in", "local", "nil", "not", "or", "repeat", "return", "then", "true", "until",
+    ];
+
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS.contains(&key)
+}
+
+fn json_string(bytes: &[u8], out: &mut String) {
+    out.push('"');
+    for ch in String::from_utf8_lossy(bytes).chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+pub(crate) fn lua_string(bytes: &[u8], out: &mut String) {
+    let valid_utf8 = std::str::from_utf8(bytes).is_ok();
+
+    out.push('"');
+    if valid_utf8 {
+        for ch in String::from_utf8_lossy(bytes).chars() {
+            match ch {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if (c as u32) < 0x20 || c as u32 == 0x7f => {
+                    out.push_str(&format!("\\{:03}", c as u32))
+                }
+                c => out.push(c),
+            }
+        }
+    } else {
+        for &byte in bytes {
+            match byte {
+                b'"' => out.push_str("\\\""),
+                b'\\' => out.push_str("\\\\"),
+                0x20..=0x7e => out.push(byte as char),
+                _ => out.push_str(&format!("\\{:03}", byte)),
+            }
+        }
+    }
+    out.push('"');
+}
+
+pub(crate) fn format_number(n: f64, format: Format) -> String {
+    if n.is_nan() {
+        return match format {
+            Format::Json => "null".into(),
+            Format::Lua => "0/0".into(),
+        };
+    }
+
+    if n.is_infinite() {
+        return match (format, n > 0.0) {
+            (Format::Json, _) => "null".into(),
+            // Not `math.huge`: STR_UNPACK loads literals with an empty environment.
+            (Format::Lua, true) => "1/0".into(),
+            (Format::Lua, false) => "-1/0".into(),
+        };
+    }
+
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        format!("{}", n)
+    }
+}
+
+impl Writer {
+    fn error(&self, msg: &str) -> mlua::Error {
+        let at = if self.path.is_empty() {
+            "<root>".to_string()
+        } else {
+            self.path.join(".")
+        };
+
+        mlua::Error::RuntimeError(format!("[INSOLENCE] Error: {} at {}", msg, at))
+    }
+
+    fn newline(&mut self, depth: usize) {
+        if let Some(indent) = self.opts.indent {
+            self.out.push('\n');
+            self.out.push_str(&" ".repeat(indent * depth));
+        }
+    }
+
+    // Returns false when the value should be left out entirely.
+    fn is_writable(&self, value: &LuaValue) -> LuaResult<bool> {
+        match value {
+            LuaValue::Nil
+            | LuaValue::Boolean(_)
+            | LuaValue::Integer(_)
+            | LuaValue::Number(_)
+            | LuaValue::String(_)
+            | LuaValue::Table(_) => Ok(true),
+            other => match self.opts.unsupported {
+                Unsupported::Skip => Ok(false),
+                Unsupported::Placeholder => Ok(true),
+                Unsupported::Error => {
+                    Err(self.error(&format!("cannot serialize a {}", other.type_name())))
+                }
+            },
+        }
+    }
+
+    fn write_value(&mut self, value: &LuaValue, depth: usize) -> LuaResult<()> {
+        match value {
+            LuaValue::Nil => self.out.push_str(match self.format {
+                Format::Json => "null",
+                Format::Lua => "nil",
+            }),
+            LuaValue::Boolean(b) => self.out.push_str(if *b { "true" } else { "false" }),
+            LuaValue::Integer(n) => self.out.push_str(&n.to_string()),
+            LuaValue::Number(n) => self.out.push_str(&format_number(*n, self.format)),
+            LuaValue::String(s) => match self.format {
+                Format::Json => json_string(&s.as_bytes(), &mut self.out),
+                Format::Lua => lua_string(&s.as_bytes(), &mut self.out),
+            },
+            LuaValue::Table(tbl) => self.write_table(tbl, depth)?,
+            other => {
+                let placeholder = format!("<{}>", other.type_name());
+                match self.format {
+                    Format::Json => json_string(placeholder.as_bytes(), &mut self.out),
+                    Format::Lua => lua_string(placeholder.as_bytes(), &mut self.out),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_table(&mut self, tbl: &LuaTable, depth: usize) -> LuaResult<()> {
+        if self.stack.contains(&tbl.to_pointer()) {
+            return Err(self.error("cycle detected"));
+        }
+        self.stack.push(tbl.to_pointer());
+
+        let mut entries: Vec<(LuaValue, LuaValue)> = Vec::new();
+        for (k, v, _) in ordered_entries(tbl)? {
+            self.path.push(
+                k.to_string()
+                    .unwrap_or_else(|_| format!("<{}>", k.type_name())),
+            );
+            let writable = self.is_writable(&k)? && self.is_writable(&v)?;
+            self.path.pop();
+
+            if writable {
+                entries.push((k, v));
+            }
+        }
+
+        let as_array = !self.opts.arrays_as_objects
+            && if entries.is_empty() {
+                self.opts.empty_as_array
+            } else {
+                is_sequence(tbl)? && entries.len() == tbl.raw_len()
+            };
+
+        if self.opts.sort_keys && !as_array {
+            entries.sort_by(|(a, _), (b, _)| compare_values(a, b));
+        }
+
+        let (open, close) = match (self.format, as_array) {
+            (Format::Json, true) => ('[', ']'),
+            (Format::Json, false) | (Format::Lua, _) => ('{', '}'),
+        };
+
+        self.out.push(open);
+        for (idx, (k, v)) in entries.iter().enumerate() {
+            if idx > 0 {
+                self.out.push(',');
+            }
+            self.newline(depth + 1);
+
+            let key_text = k
+                .to_string()
+                .unwrap_or_else(|_| format!("<{}>", k.type_name()));
+            self.path.push(key_text.clone());
+
+            if !as_array || self.format == Format::Lua && self.opts.style == LiteralStyle::StrPack {
+                self.write_key(k, &key_text)?;
+            }
+            self.write_value(v, depth + 1)?;
+
+            self.path.pop();
+        }
+        if !entries.is_empty() {
+            if self.format == Format::Lua && self.opts.style == LiteralStyle::StrPack {
+                self.out.push(',');
+            }
+            self.newline(depth);
+        }
+        self.out.push(close);
+
+        self.stack.pop();
+        Ok(())
+    }
+
+    fn write_key(&mut self, key: &LuaValue, key_text: &str) -> LuaResult<()> {
+        let spaced = self.opts.indent.is_some();
+
+        match self.format {
+            Format::Json => {
+                match key {
+                    LuaValue::String(s) => json_string(&s.as_bytes(), &mut self.out),
+                    LuaValue::Table(_) => {
+                        return Err(self.error("cannot use a table as a JSON key"));
+                    }
+                    _ => json_string(key_text.as_bytes(), &mut self.out),
+                }
+                self.out.push_str(if spaced { ": " } else { ":" });
+            }
+            Format::Lua => {
+                match key {
+                    LuaValue::String(s)
+                        if self.opts.style == LiteralStyle::Readable
+                            && is_identifier(&s.to_string_lossy()) =>
+                    {
+                        self.out.push_str(&s.to_string_lossy());
+                    }
+                    LuaValue::Table(_) => return Err(self.error("cannot use a table as a key")),
+                    _ => {
+                        self.out.push('[');
+                        self.write_value(key, 0)?;
+                        self.out.push(']');
+                    }
+                }
+                self.out.push_str(if spaced { " = " } else { "=" });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn serialize(value: &LuaValue, format: Format, opts: Option<&LuaTable>) -> LuaResult<String> {
+    let opts = WriteOpts::from_lua(opts)?;
+    let prefix = if format == Format::Lua && opts.with_return {
+        "return "
+    } else {
+        ""
+    };
+
+    let mut writer = Writer {
+        format,
+        opts,
+        out: prefix.to_string(),
+        stack: Vec::new(),
+        path: Vec::new(),
+    };
+
+    if writer.is_writable(value)? {
+        writer.write_value(value, 0)?;
+    }
+
+    Ok(writer.out)
+}
+
+// Compact, key-sorted Lua literal for log output; never fails.
+pub(crate) fn preview(value: &LuaValue) -> String {
+    let mut writer = Writer {
+        format: Format::Lua,
+        opts: WriteOpts {
+            sort_keys: true,
+            unsupported: Unsupported::Placeholder,
+            ..WriteOpts::defaults()
+        },
+        out: String::new(),
+        stack: Vec::new(),
+        path: Vec::new(),
+    };
+
+    match writer.write_value(value, 0) {
+        Ok(()) => writer.out,
+        Err(_) => format!("<{}>", value.type_name()),
+    }
+}
+
+pub(crate) fn to_json(_: &Lua, (value, opts): (LuaValue, Option<LuaTable>)) -> LuaResult<String> {
+    serialize(&value, Format::Json, opts.as_ref())
+}
+
+pub(crate) fn to_lua_literal(
+    _: &Lua,
+    (value, opts): (LuaValue, Option<LuaTable>),
+) -> LuaResult<String> {
+    serialize(&value, Format::Lua, opts.as_ref())
+}
+
+struct Parser<'a> {
+    lua: &'a Lua,
+    src: &'a [u8],
+    pos: usize,
+    depth: usize,
+    null: LuaValue,
+    what: &'static str,
+}
+
+impl<'a> Parser<'a> {
+    fn new(lua: &'a Lua, src: &'a [u8], what: &'static str) -> Self {
+        Parser {
+            lua,
+            src,
+            pos: 0,
+            depth: 0,
+            null: LuaValue::Nil,
+            what,
+        }
+    }
+
+    fn error(&self, msg: &str) -> mlua::Error {
+        let line = self.src[..self.pos.min(self.src.len())]
+            .iter()
+            .filter(|&&b| b == b'\n')
+            .count()
+            + 1;
+
+        mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: invalid {}: {} at line {} (byte {})",
+            self.what,
+            msg,
+            line,
+            self.pos + 1
+        ))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        self.src[self.pos..].starts_with(text.as_bytes())
+    }
+
+    fn skip_ws(&mut self, lua_comments: bool) {
+        loop {
+            while self.peek().is_some_and(|b| b.is_ascii_whitespace()) {
+                self.pos += 1;
+            }
+
+            if lua_comments && self.starts_with("--") {
+                self.pos += 2;
+                if let Some(level) = self.long_bracket_level() {
+                    let _ = self.long_bracket_body(level);
+                } else {
+                    while self.peek().is_some_and(|b| b != b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                continue;
+            }
+
+            break;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> LuaResult<()> {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", byte as char)))
+        }
+    }
+
+    fn enter(&mut self) -> LuaResult<()> {
+        self.depth += 1;
+        if self.depth > MAX_PARSE_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, lua_comments: bool) -> LuaResult<()> {
+        self.skip_ws(lua_comments);
+        if self.pos < self.src.len() {
+            return Err(self.error("unexpected trailing input"));
+        }
+        Ok(())
+    }
+
+    fn number_text(&mut self, extra: &[u8]) -> &'a str {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|b| b.is_ascii_alphanumeric() || extra.contains(&b))
+        {
+            // Only allow a sign directly after an exponent marker.
+            if (self.peek() == Some(b'+') || self.peek() == Some(b'-'))
+                && !matches!(self.src[self.pos - 1], b'e' | b'E')
+            {
+                break;
+            }
+            self.pos += 1;
+        }
+
+        std::str::from_utf8(&self.src[start..self.pos]).unwrap_or("")
+    }
+
+    // JSON
+
+    fn json_value(&mut self) -> LuaResult<LuaValue> {
+        self.skip_ws(false);
+
+        match self.peek() {
+            Some(b'{') => self.json_object(),
+            Some(b'[') => self.json_array(),
+            Some(b'"') => Ok(LuaValue::String(
+                self.lua.create_string(self.json_string()?)?,
+            )),
+            Some(b't') if self.starts_with("true") => {
+                self.pos += 4;
+                Ok(LuaValue::Boolean(true))
+            }
+            Some(b'f') if self.starts_with("false") => {
+                self.pos += 5;
+                Ok(LuaValue::Boolean(false))
+            }
+            Some(b'n') if self.starts_with("null") => {
+                self.pos += 4;
+                Ok(self.null.clone())
+            }
+            Some(b'-' | b'0'..=b'9') => {
+                let start = self.pos;
+                if self.peek() == Some(b'-') {
+                    self.pos += 1;
+                }
+                let text = self.number_text(b".+-");
+                let full = std::str::from_utf8(&self.src[start..self.pos]).unwrap_or(text);
+                full.parse::<f64>()
+                    .map(LuaValue::Number)
+                    .map_err(|_| self.error(&format!("bad number '{}'", full)))
+            }
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn json_object(&mut self) -> LuaResult<LuaValue> {
+        self.enter()?;
+        self.expect(b'{')?;
+        let tbl = self.lua.create_table()?;
+
+        self.skip_ws(false);
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            self.depth -= 1;
+            return Ok(LuaValue::Table(tbl));
+        }
+
+        loop {
+            self.skip_ws(false);
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a string key"));
+            }
+            let key = self.json_string()?;
+            self.skip_ws(false);
+            self.expect(b':')?;
+            let value = self.json_value()?;
+            tbl.raw_set(self.lua.create_string(key)?, value)?;
+
+            self.skip_ws(false);
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+
+        self.depth -= 1;
+        Ok(LuaValue::Table(tbl))
+    }
+
+    fn json_array(&mut self) -> LuaResult<LuaValue> {
+        self.enter()?;
+        self.expect(b'[')?;
+        let tbl = self.lua.create_table()?;
+        let mut idx = 0;
+
+        self.skip_ws(false);
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            self.depth -= 1;
+            return Ok(LuaValue::Table(tbl));
+        }
+
+        loop {
+            idx += 1;
+            let value = self.json_value()?;
+            tbl.raw_set(idx, value)?;
+
+            self.skip_ws(false);
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+
+        self.depth -= 1;
+        Ok(LuaValue::Table(tbl))
+    }
+
+    fn hex4(&mut self) -> LuaResult<u32> {
+        let digits = self
+            .src
+            .get(self.pos..self.pos + 4)
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(|d| u32::from_str_radix(d, 16).ok())
+            .ok_or_else(|| self.error("bad \\u escape"))?;
+        self.pos += 4;
+        Ok(digits)
+    }
+
+    fn json_string(&mut self) -> LuaResult<Vec<u8>> {
+        self.expect(b'"')?;
+        let mut out = Vec::new();
+
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let escaped = self
+                        .peek()
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    self.pos += 1;
+
+                    match escaped {
+                        b'"' => out.push(b'"'),
+                        b'\\' => out.push(b'\\'),
+                        b'/' => out.push(b'/'),
+                        b'b' => out.push(0x08),
+                        b'f' => out.push(0x0c),
+                        b'n' => out.push(b'\n'),
+                        b'r' => out.push(b'\r'),
+                        b't' => out.push(b'\t'),
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            if (0xd800..0xdc00).contains(&code) && self.starts_with("\\u") {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                code = 0x10000
+                                    + ((code - 0xd800) << 10)
+                                    + (low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+                            let ch = char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER);
+                            out.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+                        }
+                        _ => return Err(self.error("bad escape")),
+                    }
+                }
+                Some(byte) => {
+                    out.push(byte);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    // Lua literals
+
+    fn long_bracket_level(&mut self) -> Option<usize> {
+        if self.peek() != Some(b'[') {
+            return None;
+        }
+
+        let mut level = 0;
+        while self.src.get(self.pos + 1 + level) == Some(&b'=') {
+            level += 1;
+        }
+
+        if self.src.get(self.pos + 1 + level) == Some(&b'[') {
+            self.pos += level + 2;
+            Some(level)
+        } else {
+            None
+        }
+    }
+
+    fn long_bracket_body(&mut self, level: usize) -> LuaResult<Vec<u8>> {
+        let close = format!("]{}]", "=".repeat(level));
+
+        if self.peek() == Some(b'\r') {
+            self.pos += 1;
+        }
+        if self.peek() == Some(b'\n') {
+            self.pos += 1;
+        }
+
+        let start = self.pos;
+        while self.pos < self.src.len() {
+            if self.starts_with(&close) {
+                let body = self.src[start..self.pos].to_vec();
+                self.pos += close.len();
+                return Ok(body);
+            }
+            self.pos += 1;
+        }
+
+        Err(self.error("unterminated long string"))
+    }
+
+    fn lua_string(&mut self) -> LuaResult<Vec<u8>> {
+        let quote = self.peek().unwrap_or(b'"');
+        self.pos += 1;
+        let mut out = Vec::new();
+
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(b) if b == quote => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let escaped = self
+                        .peek()
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    self.pos += 1;
+
+                    match escaped {
+                        b'n' => out.push(b'\n'),
+                        b't' => out.push(b'\t'),
+                        b'r' => out.push(b'\r'),
+                        b'a' => out.push(0x07),
+                        b'b' => out.push(0x08),
+                        b'f' => out.push(0x0c),
+                        b'v' => out.push(0x0b),
+                        b'\\' | b'"' | b'\'' => out.push(escaped),
+                        // `%q` (and so STR_PACK) escapes newlines as a backslash followed by a real newline.
+                        b'\n' => out.push(b'\n'),
+                        b'\r' => {
+                            out.push(b'\n');
+                            if self.peek() == Some(b'\n') {
+                                self.pos += 1;
+                            }
+                        }
+                        b'x' => {
+                            let byte = self
+                                .src
+                                .get(self.pos..self.pos + 2)
+                                .and_then(|d| std::str::from_utf8(d).ok())
+                                .and_then(|d| u8::from_str_radix(d, 16).ok())
+                                .ok_or_else(|| self.error("bad \\x escape"))?;
+                            self.pos += 2;
+                            out.push(byte);
+                        }
+                        b'0'..=b'9' => {
+                            let mut code = (escaped - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.peek() {
+                                    Some(d @ b'0'..=b'9') => {
+                                        code = code * 10 + (d - b'0') as u32;
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            let byte = u8::try_from(code)
+                                .map_err(|_| self.error("decimal escape too large"))?;
+                            out.push(byte);
+                        }
+                        _ => return Err(self.error("bad escape")),
+                    }
+                }
+                Some(b'\n') => return Err(self.error("unfinished string")),
+                Some(byte) => {
+                    out.push(byte);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn lua_name(&mut self) -> &'a str {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|b| b.is_ascii_alphanumeric() || b == b'_')
+        {
+            self.pos += 1;
+        }
+
+        std::str::from_utf8(&self.src[start..self.pos]).unwrap_or("")
+    }
+
+    fn lua_number(&mut self) -> LuaResult<f64> {
+        let text = self.number_text(b".+-");
+        let parsed = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"))
+        {
+            i64::from_str_radix(hex, 16).ok().map(|n| n as f64)
+        } else {
+            text.parse::<f64>().ok()
+        };
+
+        parsed.ok_or_else(|| self.error(&format!("bad number '{}'", text)))
+    }
+
+    fn lua_value(&mut self) -> LuaResult<LuaValue> {
+        self.skip_ws(true);
+
+        match self.peek() {
+            Some(b'{') => self.lua_table(),
+            Some(b'"' | b'\'') => Ok(LuaValue::String(
+                self.lua.create_string(self.lua_string()?)?,
+            )),
+            Some(b'[') => match self.long_bracket_level() {
+                Some(level) => Ok(LuaValue::String(
+                    self.lua.create_string(self.long_bracket_body(level)?)?,
+                )),
+                None => Err(self.error("unexpected '['")),
+            },
+            Some(b'-') => {
+                self.pos += 1;
+                self.skip_ws(true);
+                self.enter()?;
+                let value = self.lua_value()?;
+                self.depth -= 1;
+                match value {
+                    LuaValue::Number(n) => Ok(LuaValue::Number(-n)),
+                    LuaValue::Integer(n) => Ok(LuaValue::Number(-(n as f64))),
+                    _ => Err(self.error("expected a number after '-'")),
+                }
+            }
+            Some(b'0'..=b'9' | b'.') => {
+                let value = self.lua_number()?;
+                // `0/0` is how NaN is written back out.
+                if self.peek() == Some(b'/') {
+                    self.pos += 1;
+                    return Ok(LuaValue::Number(value / self.lua_number()?));
+                }
+                Ok(LuaValue::Number(value))
+            }
+            Some(b) if b.is_ascii_alphabetic() || b == b'_' => {
+                let start = self.pos;
+                match self.lua_name() {
+                    "true" => Ok(LuaValue::Boolean(true)),
+                    "false" => Ok(LuaValue::Boolean(false)),
+                    "nil" => Ok(LuaValue::Nil),
+                    "inf" => Ok(LuaValue::Number(f64::INFINITY)),
+                    "nan" => Ok(LuaValue::Number(f64::NAN)),
+                    "math" if self.starts_with(".huge") => {
+                        self.pos += 5;
+                        Ok(LuaValue::Number(f64::INFINITY))
+                    }
+                    name => {
+                        self.pos = start;
+                        Err(self.error(&format!("'{}' is not a literal value", name)))
+                    }
+                }
+            }
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn lua_table(&mut self) -> LuaResult<LuaValue> {
+        self.enter()?;
+        self.expect(b'{')?;
+        let tbl = self.lua.create_table()?;
+        let mut next_idx = 1;
+
+        loop {
+            self.skip_ws(true);
+            if self.peek() == Some(b'}') {
+                self.pos += 1;
+                break;
+            }
+
+            if self.peek() == Some(b'[') && !matches!(self.src.get(self.pos + 1), Some(b'[' | b'='))
+            {
+                self.pos += 1;
+                let key = self.lua_value()?;
+                self.skip_ws(true);
+                self.expect(b']')?;
+                self.skip_ws(true);
+                self.expect(b'=')?;
+                let value = self.lua_value()?;
+
+                if key.is_nil() {
+                    return Err(self.error("table key is nil"));
+                }
+                tbl.raw_set(key, value)?;
+            } else {
+                let start = self.pos;
+                let name = if self
+                    .peek()
+                    .is_some_and(|b| b.is_ascii_alphabetic() || b == b'_')
+                {
+                    Some(self.lua_name())
+                } else {
+                    None
+                };
+                self.skip_ws(true);
+
+                match name {
+                    Some(name) if self.peek() == Some(b'=') && !self.starts_with("==") => {
+                        self.pos += 1;
+                        let value = self.lua_value()?;
+                        tbl.raw_set(name, value)?;
+                    }
+                    _ => {
+                        self.pos = start;
+                        let value = self.lua_value()?;
+                        tbl.raw_set(next_idx, value)?;
+                        next_idx += 1;
+                    }
+                }
+            }
+
+            self.skip_ws(true);
+            match self.peek() {
+                Some(b',' | b';') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+
+        self.depth -= 1;
+        Ok(LuaValue::Table(tbl))
+    }
+}
+
+pub(crate) fn from_json(
+    lua: &Lua,
+    (text, opts): (LuaString, Option<LuaTable>),
+) -> LuaResult<LuaValue> {
+    let bytes = text.as_bytes();
+    let mut parser = Parser::new(lua, &bytes, "JSON");
+
+    if let Some(opts) = opts {
+        parser.null = opts.get::<LuaValue>("null")?;
+    }
+
+    let value = parser.json_value()?;
+    parser.finish(false)?;
+
+    Ok(value)
+}
+
+// Parses data only; nothing in the input is ever executed, so untrusted files are safe to read.
+pub(crate) fn from_lua_literal(lua: &Lua, text: LuaString) -> LuaResult<LuaValue> {
+    parse_lua_literal(lua, &text.as_bytes())
+}
+
+pub(crate) fn parse_lua_literal(lua: &Lua, bytes: &[u8]) -> LuaResult<LuaValue> {
+    let mut parser = Parser::new(lua, bytes, "Lua literal");
+
+    parser.skip_ws(true);
+    if parser.starts_with("return")
+        && !parser
+            .src
+            .get(parser.pos + 6)
+            .is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'_')
+    {
+        parser.pos += 6;
+    }
+
+    let value = parser.lua_value()?;
+    parser.finish(true)?;
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infinities_load_without_globals() {
+        let lua = Lua::new();
+        let value: LuaTable = lua.load("return {1/0, -1/0}").eval().unwrap();
+        let opts = lua.create_table().unwrap();
+        opts.set("style", "str_pack").unwrap();
+
+        let literal = to_lua_literal(&lua, (LuaValue::Table(value), Some(opts))).unwrap();
+        let loaded: LuaTable = lua
+            .load(&literal)
+            .set_environment(lua.create_table().unwrap())
+            .eval()
+            .unwrap();
+
+        assert_eq!(loaded.raw_get::<f64>(1).unwrap(), f64::INFINITY);
+        assert_eq!(loaded.raw_get::<f64>(2).unwrap(), f64::NEG_INFINITY);
+
+        let parsed = parse_lua_literal(&lua, literal.as_bytes()).unwrap();
+        let parsed = parsed.as_table().unwrap();
+        assert_eq!(parsed.raw_get::<f64>(1).unwrap(), f64::INFINITY);
+        assert_eq!(parsed.raw_get::<f64>(2).unwrap(), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn unsupported_values_report_their_own_path() {
+        let lua = Lua::new();
+        let value: LuaValue = lua.load("return {a = {f = print}}").eval().unwrap();
+
+        let err = to_json(&lua, (value, None)).unwrap_err().to_string();
+        assert!(
+            err.contains("cannot serialize a function at a.f"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn json_skips_nil_holes() {
+        let lua = Lua::new();
+        let value: LuaValue = lua
+            .load("return {[1] = 1, [2] = 2, [4] = 4}")
+            .eval()
+            .unwrap();
+        let opts = lua.create_table().unwrap();
+        opts.set("sort_keys", true).unwrap();
+
+        let json = to_json(&lua, (value, Some(opts))).unwrap();
+        assert_eq!(json, r#"{"1":1,"2":2,"4":4}"#);
+    }
+}
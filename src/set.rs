@@ -0,0 +1,443 @@
+use crate::array::{Holes, read_array};
+use crate::tbl::as_number;
+use mlua::prelude::*;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::ffi::c_void;
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub(crate) enum ValueKey {
+    Nil,
+    Bool(bool),
+    Number(u64),
+    Bytes(Vec<u8>),
+    Ref(*const c_void),
+}
+
+impl ValueKey {
+    // Numbers compare by value so 1 and 1.0 collapse together, like table keys do in Lua.
+    pub(crate) fn from_value(value: &LuaValue) -> Self {
+        match value {
+            LuaValue::Nil => ValueKey::Nil,
+            LuaValue::Boolean(b) => ValueKey::Bool(*b),
+            LuaValue::Integer(_) | LuaValue::Number(_) => {
+                let n = as_number(value).unwrap_or(0.0);
+                ValueKey::Number(if n == 0.0 { 0 } else { n.to_bits() })
+            }
+            LuaValue::String(s) => ValueKey::Bytes(s.as_bytes().to_vec()),
+            other => ValueKey::Ref(other.to_pointer()),
+        }
+    }
+}
+
+// Elements in insertion order with their counts; a plain set keeps every count at 1.
+#[derive(Clone, Default)]
+pub(crate) struct Bag {
+    entries: Vec<(ValueKey, LuaValue, usize)>,
+    index: HashMap<ValueKey, usize>,
+}
+
+impl Bag {
+    fn from_values(values: Vec<LuaValue>, unique: bool) -> LuaResult<Self> {
+        let mut bag = Bag::default();
+        for value in values {
+            if !unique || !bag.contains(&value) {
+                bag.insert(value, 1)?;
+            }
+        }
+
+        Ok(bag)
+    }
+
+    fn insert(&mut self, value: LuaValue, count: usize) -> LuaResult<()> {
+        if value.is_nil() || as_number(&value).is_some_and(f64::is_nan) {
+            return Err(mlua::Error::RuntimeError(format!(
+                "[INSOLENCE] Error: cannot add {} to a set",
+                value.to_string().unwrap_or_default()
+            )));
+        }
+
+        let key = ValueKey::from_value(&value);
+        match self.index.get(&key) {
+            Some(&idx) => self.entries[idx].2 += count,
+            None if count > 0 => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value, count));
+            }
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    fn remove(&mut self, value: &LuaValue, count: usize) -> usize {
+        let Some(&idx) = self.index.get(&ValueKey::from_value(value)) else {
+            return 0;
+        };
+
+        let held = self.entries[idx].2;
+        if count < held {
+            self.entries[idx].2 -= count;
+            return count;
+        }
+
+        let (key, _, _) = self.entries.remove(idx);
+        self.index.remove(&key);
+        for (later, (key, _, _)) in self.entries.iter().enumerate().skip(idx) {
+            self.index.insert(key.clone(), later);
+        }
+
+        held
+    }
+
+    fn count(&self, value: &LuaValue) -> usize {
+        self.index
+            .get(&ValueKey::from_value(value))
+            .map_or(0, |&idx| self.entries[idx].2)
+    }
+
+    fn contains(&self, value: &LuaValue) -> bool {
+        self.index.contains_key(&ValueKey::from_value(value))
+    }
+
+    pub(crate) fn contains_str(&self, value: &str) -> bool {
+        self.index
+            .contains_key(&ValueKey::Bytes(value.as_bytes().to_vec()))
+    }
+
+    fn total(&self) -> usize {
+        self.entries.iter().map(|(_, _, count)| count).sum()
+    }
+
+    fn values(&self) -> Vec<LuaValue> {
+        self.entries
+            .iter()
+            .map(|(_, value, _)| value.clone())
+            .collect()
+    }
+
+    fn expanded(&self) -> Vec<LuaValue> {
+        self.entries
+            .iter()
+            .flat_map(|(_, value, count)| std::iter::repeat_n(value.clone(), *count))
+            .collect()
+    }
+
+    // Walks both bags in order (this one first) and keeps every element whose combined count is
+    // non-zero.
+    fn combine(&self, other: &Bag, op: impl Fn(usize, usize) -> usize) -> Bag {
+        let mut result = Bag::default();
+        let others = other
+            .entries
+            .iter()
+            .filter(|(key, _, _)| !self.index.contains_key(key));
+
+        for (key, value, _) in self.entries.iter().chain(others) {
+            let mine = self.index.get(key).map_or(0, |&idx| self.entries[idx].2);
+            let theirs = other.index.get(key).map_or(0, |&idx| other.entries[idx].2);
+            let combined = op(mine, theirs);
+
+            if combined > 0 {
+                result.index.insert(key.clone(), result.entries.len());
+                result.entries.push((key.clone(), value.clone(), combined));
+            }
+        }
+
+        result
+    }
+
+    fn is_subset(&self, other: &Bag) -> bool {
+        self.entries.iter().all(|(key, _, count)| {
+            other
+                .index
+                .get(key)
+                .is_some_and(|&idx| other.entries[idx].2 >= *count)
+        })
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Set(Bag);
+
+#[derive(Clone)]
+pub(crate) struct Multiset(Bag);
+
+// Sets, multisets and plain Lua arrays are all accepted wherever another collection is expected.
+pub(crate) fn to_bag(value: &LuaValue, unique: bool) -> LuaResult<Bag> {
+    match value {
+        LuaValue::UserData(ud) => {
+            if let Ok(set) = ud.borrow::<Set>() {
+                Ok(set.0.clone())
+            } else {
+                let multiset = ud.borrow::<Multiset>()?;
+                if unique {
+                    Bag::from_values(multiset.0.values(), true)
+                } else {
+                    Ok(multiset.0.clone())
+                }
+            }
+        }
+        LuaValue::Table(tbl) => Bag::from_values(read_array(tbl, Holes::Skip)?, unique),
+        LuaValue::Nil => Ok(Bag::default()),
+        other => Err(mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: expected a set or array, got {}",
+            other.type_name()
+        ))),
+    }
+}
+
+fn iterator(lua: &Lua, items: Vec<(LuaValue, usize)>) -> LuaResult<LuaFunction> {
+    let position = Cell::new(0);
+
+    lua.create_function(move |_, ()| {
+        let idx = position.get();
+        position.set(idx + 1);
+
+        Ok(match items.get(idx) {
+            Some((value, count)) => (value.clone(), Some(*count)),
+            None => (LuaValue::Nil, None),
+        })
+    })
+}
+
+fn describe(name: &str, bag: &Bag) -> String {
+    let items: Vec<String> = bag
+        .entries
+        .iter()
+        .map(|(_, value, count)| {
+            let text = match value {
+                LuaValue::String(s) => format!("\"{}\"", s.to_string_lossy()),
+                other => other
+                    .to_string()
+                    .unwrap_or_else(|_| other.type_name().into()),
+            };
+
+            if name == "Multiset" {
+                format!("{} x{}", text, count)
+            } else {
+                text
+            }
+        })
+        .collect();
+
+    format!("{}({})", name, items.join(", "))
+}
+
+impl LuaUserData for Set {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("size", |_, this| Ok(this.0.entries.len()));
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method_mut("add", |_, this, values: LuaVariadic<LuaValue>| {
+            for value in values {
+                if !this.0.contains(&value) {
+                    this.0.insert(value, 1)?;
+                }
+            }
+            Ok(())
+        });
+        methods.add_method_mut("remove", |_, this, value: LuaValue| {
+            Ok(this.0.remove(&value, 1) > 0)
+        });
+        methods.add_method_mut("clear", |_, this, ()| {
+            this.0 = Bag::default();
+            Ok(())
+        });
+        methods.add_method("has", |_, this, value: LuaValue| {
+            Ok(this.0.contains(&value))
+        });
+        methods.add_method("union", |_, this, other: LuaValue| {
+            Ok(Set(this.0.combine(&to_bag(&other, true)?, usize::max)))
+        });
+        methods.add_method("intersection", |_, this, other: LuaValue| {
+            Ok(Set(this.0.combine(&to_bag(&other, true)?, usize::min)))
+        });
+        methods.add_method("difference", |_, this, other: LuaValue| {
+            Ok(Set(this
+                .0
+                .combine(&to_bag(&other, true)?, usize::saturating_sub)))
+        });
+        methods.add_method("symmetric_difference", |_, this, other: LuaValue| {
+            Ok(Set(this.0.combine(&to_bag(&other, true)?, |a, b| a ^ b)))
+        });
+        methods.add_method("is_subset", |_, this, other: LuaValue| {
+            Ok(this.0.is_subset(&to_bag(&other, true)?))
+        });
+        methods.add_method("is_superset", |_, this, other: LuaValue| {
+            Ok(to_bag(&other, true)?.is_subset(&this.0))
+        });
+        methods.add_method("is_disjoint", |_, this, other: LuaValue| {
+            let other = to_bag(&other, true)?;
+            Ok(!this
+                .0
+                .entries
+                .iter()
+                .any(|(key, _, _)| other.index.contains_key(key)))
+        });
+        methods.add_method("copy", |_, this, ()| Ok(this.clone()));
+        methods.add_method("to_array", |lua, this, ()| {
+            lua.create_sequence_from(this.0.values())
+        });
+        methods.add_method("iter", |lua, this, ()| {
+            iterator(
+                lua,
+                this.0
+                    .entries
+                    .iter()
+                    .map(|(_, v, _)| (v.clone(), 1))
+                    .collect(),
+            )
+        });
+
+        methods.add_meta_method(LuaMetaMethod::Len, |_, this, ()| Ok(this.0.entries.len()));
+        methods.add_meta_function(LuaMetaMethod::Add, |_, (a, b): (LuaValue, LuaValue)| {
+            Ok(Set(
+                to_bag(&a, true)?.combine(&to_bag(&b, true)?, usize::max)
+            ))
+        });
+        methods.add_meta_function(LuaMetaMethod::Mul, |_, (a, b): (LuaValue, LuaValue)| {
+            Ok(Set(
+                to_bag(&a, true)?.combine(&to_bag(&b, true)?, usize::min)
+            ))
+        });
+        methods.add_meta_function(LuaMetaMethod::Sub, |_, (a, b): (LuaValue, LuaValue)| {
+            Ok(Set(
+                to_bag(&a, true)?.combine(&to_bag(&b, true)?, usize::saturating_sub)
+            ))
+        });
+        methods.add_meta_function(LuaMetaMethod::Le, |_, (a, b): (LuaValue, LuaValue)| {
+            Ok(to_bag(&a, true)?.is_subset(&to_bag(&b, true)?))
+        });
+        methods.add_meta_method(LuaMetaMethod::Eq, |_, this, other: LuaAnyUserData| {
+            Ok(other.borrow::<Set>().is_ok_and(|other| {
+                this.0.entries.len() == other.0.entries.len() && this.0.is_subset(&other.0)
+            }))
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(describe("Set", &this.0))
+        });
+    }
+}
+
+impl LuaUserData for Multiset {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("size", |_, this| Ok(this.0.total()));
+        fields.add_field_method_get("distinct", |_, this| Ok(this.0.entries.len()));
+    }
+
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method_mut(
+            "add",
+            |_, this, (value, count): (LuaValue, Option<usize>)| {
+                this.0.insert(value, count.unwrap_or(1))
+            },
+        );
+        methods.add_method_mut(
+            "remove",
+            |_, this, (value, count): (LuaValue, Option<usize>)| {
+                Ok(this.0.remove(&value, count.unwrap_or(1)))
+            },
+        );
+        methods.add_method_mut("clear", |_, this, ()| {
+            this.0 = Bag::default();
+            Ok(())
+        });
+        methods.add_method("count", |_, this, value: LuaValue| Ok(this.0.count(&value)));
+        methods.add_method("has", |_, this, value: LuaValue| {
+            Ok(this.0.contains(&value))
+        });
+        methods.add_method("union", |_, this, other: LuaValue| {
+            Ok(Multiset(
+                this.0.combine(&to_bag(&other, false)?, usize::max),
+            ))
+        });
+        methods.add_method("sum", |_, this, other: LuaValue| {
+            Ok(Multiset(
+                this.0.combine(&to_bag(&other, false)?, |a, b| a + b),
+            ))
+        });
+        methods.add_method("intersection", |_, this, other: LuaValue| {
+            Ok(Multiset(
+                this.0.combine(&to_bag(&other, false)?, usize::min),
+            ))
+        });
+        methods.add_method("difference", |_, this, other: LuaValue| {
+            Ok(Multiset(
+                this.0
+                    .combine(&to_bag(&other, false)?, usize::saturating_sub),
+            ))
+        });
+        methods.add_method("is_subset", |_, this, other: LuaValue| {
+            Ok(this.0.is_subset(&to_bag(&other, false)?))
+        });
+        methods.add_method("is_superset", |_, this, other: LuaValue| {
+            Ok(to_bag(&other, false)?.is_subset(&this.0))
+        });
+        methods.add_method("most_common", |lua, this, limit: Option<usize>| {
+            let mut ranked: Vec<&(ValueKey, LuaValue, usize)> = this.0.entries.iter().collect();
+            ranked.sort_by_key(|entry| std::cmp::Reverse(entry.2));
+
+            let result = lua.create_table()?;
+            for (_, value, count) in ranked.into_iter().take(limit.unwrap_or(usize::MAX)) {
+                let entry = lua.create_table()?;
+                entry.set("value", value.clone())?;
+                entry.set("count", *count)?;
+                result.raw_push(entry)?;
+            }
+            Ok(result)
+        });
+        methods.add_method("counts", |lua, this, ()| {
+            let result = lua.create_table()?;
+            for (_, value, count) in &this.0.entries {
+                result.raw_set(value.clone(), *count)?;
+            }
+            Ok(result)
+        });
+        methods.add_method("copy", |_, this, ()| Ok(this.clone()));
+        methods.add_method("to_set", |_, this, ()| {
+            Ok(Set(Bag::from_values(this.0.values(), true)?))
+        });
+        methods.add_method("to_array", |lua, this, ()| {
+            lua.create_sequence_from(this.0.expanded())
+        });
+        methods.add_method("iter", |lua, this, ()| {
+            iterator(
+                lua,
+                this.0
+                    .entries
+                    .iter()
+                    .map(|(_, v, c)| (v.clone(), *c))
+                    .collect(),
+            )
+        });
+
+        methods.add_meta_method(LuaMetaMethod::Len, |_, this, ()| Ok(this.0.total()));
+        methods.add_meta_function(LuaMetaMethod::Add, |_, (a, b): (LuaValue, LuaValue)| {
+            Ok(Multiset(
+                to_bag(&a, false)?.combine(&to_bag(&b, false)?, |a, b| a + b),
+            ))
+        });
+        methods.add_meta_function(LuaMetaMethod::Sub, |_, (a, b): (LuaValue, LuaValue)| {
+            Ok(Multiset(
+                to_bag(&a, false)?.combine(&to_bag(&b, false)?, usize::saturating_sub),
+            ))
+        });
+        methods.add_meta_method(LuaMetaMethod::Eq, |_, this, other: LuaAnyUserData| {
+            Ok(other
+                .borrow::<Multiset>()
+                .is_ok_and(|other| this.0.total() == other.0.total() && this.0.is_subset(&other.0)))
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(describe("Multiset", &this.0))
+        });
+    }
+}
+
+pub(crate) fn set(_: &Lua, values: Option<LuaValue>) -> LuaResult<Set> {
+    Ok(Set(to_bag(&values.unwrap_or(LuaValue::Nil), true)?))
+}
+
+pub(crate) fn multiset(_: &Lua, values: Option<LuaValue>) -> LuaResult<Multiset> {
+    Ok(Multiset(to_bag(&values.unwrap_or(LuaValue::Nil), false)?))
+}
@@ -1,4 +1,8 @@
+use crate::array::{Holes, read_array, write_array};
 use mlua::prelude::*;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::ffi::c_void;
 
 pub(crate) fn largest_val(_: &Lua, tbl: LuaTable) -> LuaResult<Option<LuaValue>> {
     if tbl.is_empty() {
@@ -11,49 +15,56 @@ pub(crate) fn largest_val(_: &Lua, tbl: LuaTable) -> LuaResult<Option<LuaValue>>
     for pair in tbl.pairs::<LuaValue, LuaValue>() {
         let (k, v) = pair?;
 
-        if let LuaValue::Number(n) = v {
-            let num = n;
-            if max_val.is_none() || num > max_val.unwrap() {
-                max_val = Some(num);
-                max_key = Some(k);
-            }
+        if let Some(num) = as_number(&v)
+            && max_val.is_none_or(|max| num > max)
+        {
+            max_val = Some(num);
+            max_key = Some(k);
         }
     }
 
     Ok(max_key)
 }
 
-pub(crate) fn average_table_amt(_: &Lua, tbl: LuaTable) -> LuaResult<f64> {
-    let mut sum = 0.0;
-    let mut count = 0;
+pub(crate) fn smallest_val(_: &Lua, tbl: LuaTable) -> LuaResult<Option<LuaValue>> {
+    let mut min_key: Option<LuaValue> = None;
+    let mut min_val: Option<f64> = None;
 
     for pair in tbl.pairs::<LuaValue, LuaValue>() {
-        let (_, v) = pair?;
+        let (k, v) = pair?;
 
-        if let LuaValue::Number(n) = v {
-            sum += n;
-            count += 1;
+        if let Some(num) = as_number(&v)
+            && min_val.is_none_or(|min| num < min)
+        {
+            min_val = Some(num);
+            min_key = Some(k);
         }
     }
 
-    if count == 0 {
-        Ok(3.0)
-    } else {
-        Ok(sum / (count as f64))
-    }
+    Ok(min_key)
 }
 
-pub(crate) fn reverse_table(lua: &Lua, tbl: LuaTable) -> LuaResult<LuaTable> {
-    let reversed = lua.create_table()?;
-    let len = tbl.len()?;
+// Mean of the numeric values, skipping anything else; nil for a table with no numbers.
+pub(crate) fn average_table_amt(_: &Lua, tbl: LuaTable) -> LuaResult<Option<f64>> {
+    let entries = numeric_entries(&tbl, &[], true)?;
+    if entries.is_empty() {
+        return Ok(None);
+    }
 
-    for i in (1..=len).rev() {
-        let val = tbl.get::<LuaValue>(i)?;
+    Ok(Some(
+        entries.iter().map(|(_, n)| n).sum::<f64>() / entries.len() as f64,
+    ))
+}
 
-        reversed.push(val)?;
-    }
+pub(crate) fn reverse_table(
+    lua: &Lua,
+    (tbl, opts): (LuaTable, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let holes = Holes::from_opts(opts.as_ref())?;
+    let mut values = read_array(&tbl, holes)?;
+    values.reverse();
 
-    Ok(reversed)
+    write_array(lua, values, holes)
 }
 
 pub(crate) fn mod_vals(lua: &Lua, (input, modifier): (LuaValue, f64)) -> LuaResult<LuaValue> {
@@ -73,3 +84,1654 @@ pub(crate) fn mod_vals(lua: &Lua, (input, modifier): (LuaValue, f64)) -> LuaResu
         Ok(input)
     }
 }
+
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// "odds" or "h_*" match a key anywhere in the path; "extra.*" is matched from the root.
+// A pattern that matches a table also covers everything below it.
+struct KeyPattern(String);
+
+impl KeyPattern {
+    fn matches(&self, path: &[String]) -> bool {
+        if !self.0.contains('.') {
+            return path.iter().any(|key| glob_match(&self.0, key));
+        }
+
+        let segments: Vec<&str> = self.0.split('.').collect();
+        segments.len() <= path.len()
+            && segments
+                .iter()
+                .zip(path)
+                .all(|(pattern, key)| glob_match(pattern, key))
+    }
+
+    fn specificity(&self) -> usize {
+        self.0.chars().filter(|&c| c != '*').count()
+    }
+}
+
+struct KeyRules<T> {
+    rules: Vec<(KeyPattern, T)>,
+}
+
+impl<T> KeyRules<T> {
+    // A bare rule applies everywhere; a table maps patterns to rules, most specific first.
+    fn from_lua(value: LuaValue, parse: impl Fn(LuaValue) -> LuaResult<T>) -> LuaResult<Self> {
+        let mut rules = Vec::new();
+
+        match value {
+            LuaValue::Nil => {}
+            LuaValue::Table(tbl) => {
+                for pair in tbl.pairs::<String, LuaValue>() {
+                    let (pattern, rule) = pair?;
+                    rules.push((KeyPattern(pattern), parse(rule)?));
+                }
+            }
+            other => rules.push((KeyPattern("*".into()), parse(other)?)),
+        }
+
+        rules.sort_by(|(a, _), (b, _)| {
+            b.specificity()
+                .cmp(&a.specificity())
+                .then_with(|| a.0.cmp(&b.0))
+        });
+
+        Ok(KeyRules { rules })
+    }
+
+    fn find(&self, path: &[String]) -> Option<&T> {
+        self.rules
+            .iter()
+            .find(|(pattern, _)| pattern.matches(path))
+            .map(|(_, rule)| rule)
+    }
+}
+
+fn key_patterns(value: Option<LuaTable>) -> LuaResult<Vec<KeyPattern>> {
+    match value {
+        Some(tbl) => tbl
+            .sequence_values::<String>()
+            .map(|pattern| pattern.map(KeyPattern))
+            .collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
+enum ScaleOp {
+    Multiply(f64),
+    Add(f64),
+    Exponent(f64),
+    Custom(LuaFunction),
+}
+
+enum RoundRule {
+    Keep,
+    Floor,
+    Ceil,
+    Nearest,
+    Step(f64),
+}
+
+impl RoundRule {
+    fn from_lua(value: LuaValue) -> LuaResult<Self> {
+        match value {
+            LuaValue::String(s) => match s.to_str()?.as_ref() {
+                "none" => Ok(RoundRule::Keep),
+                "floor" => Ok(RoundRule::Floor),
+                "ceil" => Ok(RoundRule::Ceil),
+                "nearest" => Ok(RoundRule::Nearest),
+                other => Err(mlua::Error::RuntimeError(format!(
+                    "[INSOLENCE] Error: unknown rounding rule {}",
+                    other
+                ))),
+            },
+            other => match as_number(&other) {
+                Some(step) if step > 0.0 => Ok(RoundRule::Step(step)),
+                _ => Err(mlua::Error::RuntimeError(format!(
+                    "[INSOLENCE] Error: rounding rule must be a name or a positive step, got {}",
+                    other.type_name()
+                ))),
+            },
+        }
+    }
+
+    fn apply(&self, n: f64) -> f64 {
+        match self {
+            RoundRule::Keep => n,
+            RoundRule::Floor => n.floor(),
+            RoundRule::Ceil => n.ceil(),
+            RoundRule::Nearest => n.round(),
+            RoundRule::Step(step) => (n / step).round() * step,
+        }
+    }
+}
+
+struct Scaler {
+    op: ScaleOp,
+    include: Vec<KeyPattern>,
+    exclude: Vec<KeyPattern>,
+    round: KeyRules<RoundRule>,
+    min: KeyRules<f64>,
+    max: KeyRules<f64>,
+    seen: HashSet<*const c_void>,
+}
+
+impl Scaler {
+    fn from_lua(opts: &LuaTable) -> LuaResult<Self> {
+        let amount = opts.get::<Option<f64>>("amount")?;
+        let op = match opts.get::<LuaValue>("op")? {
+            LuaValue::Function(func) => ScaleOp::Custom(func),
+            LuaValue::Nil => ScaleOp::Multiply(amount.unwrap_or(1.0)),
+            LuaValue::String(name) => match name.to_str()?.as_ref() {
+                "multiply" => ScaleOp::Multiply(amount.unwrap_or(1.0)),
+                "add" => ScaleOp::Add(amount.unwrap_or(0.0)),
+                "exponent" => ScaleOp::Exponent(amount.unwrap_or(1.0)),
+                other => {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        "[INSOLENCE] Error: unknown scale op {}",
+                        other
+                    )));
+                }
+            },
+            other => {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "[INSOLENCE] Error: scale op must be a name or function, got {}",
+                    other.type_name()
+                )));
+            }
+        };
+
+        let number = |value: LuaValue| {
+            as_number(&value).ok_or_else(|| {
+                mlua::Error::RuntimeError(format!(
+                    "[INSOLENCE] Error: expected a number limit, got {}",
+                    value.type_name()
+                ))
+            })
+        };
+
+        Ok(Scaler {
+            op,
+            include: key_patterns(opts.get("include")?)?,
+            exclude: key_patterns(opts.get("exclude")?)?,
+            round: KeyRules::from_lua(opts.get("round")?, RoundRule::from_lua)?,
+            min: KeyRules::from_lua(opts.get("min")?, number)?,
+            max: KeyRules::from_lua(opts.get("max")?, number)?,
+            seen: HashSet::new(),
+        })
+    }
+
+    fn is_selected(&self, path: &[String]) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.matches(path)))
+            && !self.exclude.iter().any(|p| p.matches(path))
+    }
+
+    fn scale_number(
+        &self,
+        value: &LuaValue,
+        key: &LuaValue,
+        path: &[String],
+    ) -> LuaResult<LuaValue> {
+        let Some(n) = as_number(value) else {
+            return Ok(value.clone());
+        };
+
+        let mut scaled = match &self.op {
+            ScaleOp::Multiply(amount) => n * amount,
+            ScaleOp::Add(amount) => n + amount,
+            ScaleOp::Exponent(amount) => n.powf(*amount),
+            ScaleOp::Custom(func) => {
+                match func.call::<Option<f64>>((value.clone(), key.clone(), path.join(".")))? {
+                    Some(result) => result,
+                    None => return Ok(value.clone()),
+                }
+            }
+        };
+
+        if let Some(rule) = self.round.find(path) {
+            scaled = rule.apply(scaled);
+        }
+        if let Some(min) = self.min.find(path) {
+            scaled = scaled.max(*min);
+        }
+        if let Some(max) = self.max.find(path) {
+            scaled = scaled.min(*max);
+        }
+
+        Ok(match value {
+            LuaValue::Integer(_) if scaled.fract() == 0.0 && scaled.abs() < 9.0e15 => {
+                LuaValue::Integer(scaled as i64)
+            }
+            _ => LuaValue::Number(scaled),
+        })
+    }
+
+    fn scale_table(&mut self, tbl: &LuaTable, path: &mut Vec<String>) -> LuaResult<()> {
+        if !self.seen.insert(tbl.to_pointer()) {
+            return Ok(());
+        }
+
+        let mut updates = Vec::new();
+        for pair in tbl.pairs::<LuaValue, LuaValue>() {
+            let (k, v) = pair?;
+            path.push(
+                k.to_string()
+                    .unwrap_or_else(|_| format!("<{}>", k.type_name())),
+            );
+
+            match &v {
+                LuaValue::Table(child) => self.scale_table(child, path)?,
+                LuaValue::Integer(_) | LuaValue::Number(_) if self.is_selected(path) => {
+                    updates.push((k.clone(), self.scale_number(&v, &k, path)?));
+                }
+                _ => {}
+            }
+
+            path.pop();
+        }
+
+        for (k, v) in updates {
+            tbl.raw_set(k, v)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn scale_values(
+    lua: &Lua,
+    (input, opts): (LuaValue, Option<LuaTable>),
+) -> LuaResult<LuaValue> {
+    let opts = match opts {
+        Some(opts) => opts,
+        None => lua.create_table()?,
+    };
+    let mut scaler = Scaler::from_lua(&opts)?;
+
+    match input {
+        LuaValue::Table(tbl) => {
+            let target = if opts.get::<Option<bool>>("in_place")?.unwrap_or(false) {
+                tbl
+            } else {
+                DeepCopier::new(lua, MetatableMode::Keep).copy_table(&tbl)?
+            };
+
+            scaler.scale_table(&target, &mut Vec::new())?;
+            Ok(LuaValue::Table(target))
+        }
+        other => scaler.scale_number(&other, &LuaValue::Nil, &[]),
+    }
+}
+
+pub(crate) fn as_number(value: &LuaValue) -> Option<f64> {
+    match value {
+        LuaValue::Number(n) => Some(*n),
+        LuaValue::Integer(n) => Some(*n as f64),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum PathSegment {
+    Key(String),
+    Index(i64),
+    Wildcard,
+}
+
+impl PathSegment {
+    fn get(&self, tbl: &LuaTable) -> LuaResult<LuaValue> {
+        match self {
+            PathSegment::Key(key) => tbl.get(key.as_str()),
+            PathSegment::Index(idx) => tbl.get(*idx),
+            PathSegment::Wildcard => tbl.get("*"),
+        }
+    }
+
+    fn set(&self, tbl: &LuaTable, value: LuaValue) -> LuaResult<()> {
+        match self {
+            PathSegment::Key(key) => tbl.set(key.as_str(), value),
+            PathSegment::Index(idx) => tbl.set(*idx, value),
+            PathSegment::Wildcard => tbl.set("*", value),
+        }
+    }
+}
+
+// Splits "G.jokers.cards.1.ability" into segments; numeric segments become integer keys.
+pub(crate) fn parse_path(path: &str) -> Vec<PathSegment> {
+    if path.is_empty() {
+        return Vec::new();
+    }
+
+    path.split('.')
+        .map(|segment| match segment {
+            "*" => PathSegment::Wildcard,
+            _ => match segment.parse::<i64>() {
+                Ok(idx) => PathSegment::Index(idx),
+                Err(_) => PathSegment::Key(segment.to_string()),
+            },
+        })
+        .collect()
+}
+
+pub(crate) fn lookup_path(root: LuaValue, path: &[PathSegment]) -> LuaResult<LuaValue> {
+    let mut current = root;
+
+    for segment in path {
+        current = match current {
+            LuaValue::Table(tbl) => segment.get(&tbl)?,
+            _ => return Ok(LuaValue::Nil),
+        };
+    }
+
+    Ok(current)
+}
+
+fn path_root(lua: &Lua, root: LuaValue) -> LuaValue {
+    match root {
+        LuaValue::Nil => LuaValue::Table(lua.globals()),
+        other => other,
+    }
+}
+
+pub(crate) fn get_path(
+    lua: &Lua,
+    (root, path, default): (LuaValue, String, LuaValue),
+) -> LuaResult<LuaValue> {
+    match lookup_path(path_root(lua, root), &parse_path(&path))? {
+        LuaValue::Nil => Ok(default),
+        found => Ok(found),
+    }
+}
+
+pub(crate) fn has_path(lua: &Lua, (root, path): (LuaValue, String)) -> LuaResult<bool> {
+    Ok(!lookup_path(path_root(lua, root), &parse_path(&path))?.is_nil())
+}
+
+pub(crate) fn set_path(
+    lua: &Lua,
+    (root, path, value): (LuaValue, String, LuaValue),
+) -> LuaResult<()> {
+    let segments = parse_path(&path);
+    let Some((last, parents)) = segments.split_last() else {
+        return Err(mlua::Error::RuntimeError(
+            "[INSOLENCE] Error: set_path needs a non-empty path".into(),
+        ));
+    };
+
+    let mut current = match path_root(lua, root) {
+        LuaValue::Table(tbl) => tbl,
+        other => {
+            return Err(mlua::Error::RuntimeError(format!(
+                "[INSOLENCE] Error: set_path root must be a table, got {}",
+                other.type_name()
+            )));
+        }
+    };
+
+    for (depth, segment) in parents.iter().enumerate() {
+        current = match segment.get(&current)? {
+            LuaValue::Table(tbl) => tbl,
+            LuaValue::Nil => {
+                let created = lua.create_table()?;
+                segment.set(&current, LuaValue::Table(created.clone()))?;
+                created
+            }
+            other => {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "[INSOLENCE] Error: cannot set {}: {} is not a table (got {})",
+                    path,
+                    path.split('.')
+                        .take(depth + 1)
+                        .collect::<Vec<_>>()
+                        .join("."),
+                    other.type_name()
+                )));
+            }
+        };
+    }
+
+    last.set(&current, value)
+}
+
+fn collect_matches(
+    value: LuaValue,
+    path: &[PathSegment],
+    trail: &mut Vec<String>,
+    found: &mut Vec<(String, LuaValue)>,
+) -> LuaResult<()> {
+    let Some((segment, rest)) = path.split_first() else {
+        if !value.is_nil() {
+            found.push((trail.join("."), value));
+        }
+        return Ok(());
+    };
+
+    let LuaValue::Table(tbl) = value else {
+        return Ok(());
+    };
+
+    if *segment == PathSegment::Wildcard {
+        for (k, v, _) in ordered_entries(&tbl)? {
+            trail.push(k.to_string()?);
+            collect_matches(v, rest, trail, found)?;
+            trail.pop();
+        }
+    } else {
+        trail.push(match segment {
+            PathSegment::Key(key) => key.clone(),
+            PathSegment::Index(idx) => idx.to_string(),
+            PathSegment::Wildcard => unreachable!(),
+        });
+        collect_matches(segment.get(&tbl)?, rest, trail, found)?;
+        trail.pop();
+    }
+
+    Ok(())
+}
+
+pub(crate) fn query_path(
+    root: LuaValue,
+    path: &[PathSegment],
+) -> LuaResult<Vec<(String, LuaValue)>> {
+    let mut found = Vec::new();
+    collect_matches(root, path, &mut Vec::new(), &mut found)?;
+
+    Ok(found)
+}
+
+pub(crate) fn query(
+    lua: &Lua,
+    (root, path): (LuaValue, String),
+) -> LuaResult<(LuaTable, LuaTable)> {
+    let found = query_path(path_root(lua, root), &parse_path(&path))?;
+    let values = lua.create_table()?;
+    let paths = lua.create_table()?;
+
+    for (found_path, value) in found {
+        values.raw_push(value)?;
+        paths.raw_push(found_path)?;
+    }
+
+    Ok((values, paths))
+}
+
+fn percentile_of(sorted: &[f64], pct: f64) -> f64 {
+    let rank = (pct / 100.0).clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+// The number found at `field` under each value, keyed by where it came from.
+fn numeric_entries(
+    tbl: &LuaTable,
+    field: &[PathSegment],
+    ignore_non_numbers: bool,
+) -> LuaResult<Vec<(LuaValue, f64)>> {
+    let mut entries = Vec::new();
+
+    for pair in tbl.pairs::<LuaValue, LuaValue>() {
+        let (k, v) = pair?;
+        let v = lookup_path(v, field)?;
+
+        match as_number(&v) {
+            Some(n) => entries.push((k, n)),
+            None if ignore_non_numbers => continue,
+            None => {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "[INSOLENCE] Error: stats value at key {} is not a number (got {})",
+                    k.to_string()?,
+                    v.type_name()
+                )));
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+pub(crate) fn stats(lua: &Lua, (tbl, opts): (LuaTable, Option<LuaTable>)) -> LuaResult<LuaValue> {
+    let mut field: Vec<PathSegment> = Vec::new();
+    let mut ignore_non_numbers = false;
+    let mut sample = false;
+    let mut percentiles: Vec<f64> = vec![25.0, 50.0, 75.0];
+
+    if let Some(opts) = &opts {
+        if let Some(path) = opts.get::<Option<String>>("field")? {
+            field = parse_path(&path);
+        }
+        ignore_non_numbers = opts
+            .get::<Option<bool>>("ignore_non_numbers")?
+            .unwrap_or(false);
+        sample = opts.get::<Option<bool>>("sample")?.unwrap_or(false);
+        if let Some(pcts) = opts.get::<Option<Vec<f64>>>("percentiles")? {
+            percentiles = pcts;
+        }
+    }
+
+    let entries = numeric_entries(&tbl, &field, ignore_non_numbers)?;
+    if entries.is_empty() {
+        return Ok(LuaValue::Nil);
+    }
+
+    let count = entries.len() as f64;
+    let sum: f64 = entries.iter().map(|(_, n)| n).sum();
+    let mean = sum / count;
+
+    let mut min_idx = 0;
+    let mut max_idx = 0;
+    for (idx, (_, n)) in entries.iter().enumerate() {
+        if *n < entries[min_idx].1 {
+            min_idx = idx;
+        }
+        if *n > entries[max_idx].1 {
+            max_idx = idx;
+        }
+    }
+
+    let mut sorted: Vec<f64> = entries.iter().map(|(_, n)| *n).collect();
+    sorted.sort_by(f64::total_cmp);
+
+    let mut mode = sorted[0];
+    let mut mode_count = 0;
+    let mut run_start = 0;
+    for idx in 1..=sorted.len() {
+        if idx == sorted.len() || sorted[idx] != sorted[run_start] {
+            if idx - run_start > mode_count {
+                mode_count = idx - run_start;
+                mode = sorted[run_start];
+            }
+            run_start = idx;
+        }
+    }
+
+    let divisor = if sample && entries.len() > 1 {
+        count - 1.0
+    } else {
+        count
+    };
+    let variance = entries.iter().map(|(_, n)| (n - mean).powi(2)).sum::<f64>() / divisor;
+
+    let pct_tbl = lua.create_table()?;
+    for pct in percentiles {
+        pct_tbl.set(pct, percentile_of(&sorted, pct))?;
+    }
+
+    let result = lua.create_table()?;
+    result.set("count", entries.len())?;
+    result.set("sum", sum)?;
+    result.set("mean", mean)?;
+    result.set("median", percentile_of(&sorted, 50.0))?;
+    result.set("mode", mode)?;
+    result.set("mode_count", mode_count)?;
+    result.set("min", entries[min_idx].1)?;
+    result.set("min_key", entries[min_idx].0.clone())?;
+    result.set("max", entries[max_idx].1)?;
+    result.set("max_key", entries[max_idx].0.clone())?;
+    result.set("variance", variance)?;
+    result.set("std_dev", variance.sqrt())?;
+    result.set("percentiles", pct_tbl)?;
+
+    Ok(LuaValue::Table(result))
+}
+
+// Largest index a double still counts exactly; anything above is not treated as an array index.
+const MAX_SEQ_INDEX: f64 = 9_007_199_254_740_992.0;
+
+pub(crate) fn seq_index(key: &LuaValue) -> Option<usize> {
+    match as_number(key) {
+        Some(n) if (1.0..=MAX_SEQ_INDEX).contains(&n) && n.fract() == 0.0 => Some(n as usize),
+        _ => None,
+    }
+}
+
+// A table whose keys are exactly 1..n, with no holes and no hash part.
+pub(crate) fn is_sequence(tbl: &LuaTable) -> LuaResult<bool> {
+    let mut count = 0;
+    let mut max = 0;
+
+    for pair in tbl.pairs::<LuaValue, LuaValue>() {
+        let (k, _) = pair?;
+
+        match seq_index(&k) {
+            Some(idx) => max = max.max(idx),
+            None => return Ok(false),
+        }
+        count += 1;
+    }
+
+    Ok(count == max)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum MetatableMode {
+    Keep,
+    Copy,
+    Drop,
+}
+
+impl MetatableMode {
+    pub(crate) fn from_opts(opts: Option<&LuaTable>) -> LuaResult<Self> {
+        let mode = match opts {
+            Some(opts) => opts.get::<Option<String>>("metatable")?,
+            None => None,
+        };
+
+        match mode.as_deref() {
+            None | Some("keep") => Ok(MetatableMode::Keep),
+            Some("copy") => Ok(MetatableMode::Copy),
+            Some("drop") => Ok(MetatableMode::Drop),
+            Some(other) => Err(mlua::Error::RuntimeError(format!(
+                "[INSOLENCE] Error: unknown metatable mode {}",
+                other
+            ))),
+        }
+    }
+}
+
+pub(crate) struct DeepCopier<'a> {
+    lua: &'a Lua,
+    metatable: MetatableMode,
+    seen: HashMap<*const c_void, LuaTable>,
+}
+
+impl<'a> DeepCopier<'a> {
+    pub(crate) fn new(lua: &'a Lua, metatable: MetatableMode) -> Self {
+        DeepCopier {
+            lua,
+            metatable,
+            seen: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn copy_value(&mut self, value: LuaValue) -> LuaResult<LuaValue> {
+        match value {
+            LuaValue::Table(tbl) => Ok(LuaValue::Table(self.copy_table(&tbl)?)),
+            other => Ok(other),
+        }
+    }
+
+    // Copies are memoised by identity, so shared sub-tables stay shared and cycles stay cycles.
+    pub(crate) fn copy_table(&mut self, tbl: &LuaTable) -> LuaResult<LuaTable> {
+        if let Some(copy) = self.seen.get(&tbl.to_pointer()) {
+            return Ok(copy.clone());
+        }
+
+        let copy = self.lua.create_table()?;
+        self.seen.insert(tbl.to_pointer(), copy.clone());
+
+        for pair in tbl.pairs::<LuaValue, LuaValue>() {
+            let (k, v) = pair?;
+            let k = self.copy_value(k)?;
+            let v = self.copy_value(v)?;
+
+            copy.raw_set(k, v)?;
+        }
+
+        match (self.metatable, tbl.metatable()) {
+            (MetatableMode::Keep, Some(mt)) => copy.set_metatable(Some(mt)),
+            (MetatableMode::Copy, Some(mt)) => {
+                let mt = self.copy_table(&mt)?;
+                copy.set_metatable(Some(mt));
+            }
+            _ => {}
+        }
+
+        Ok(copy)
+    }
+}
+
+pub(crate) fn deep_copy(
+    lua: &Lua,
+    (value, opts): (LuaValue, Option<LuaTable>),
+) -> LuaResult<LuaValue> {
+    let mode = MetatableMode::from_opts(opts.as_ref())?;
+
+    DeepCopier::new(lua, mode).copy_value(value)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ArrayStrategy {
+    Replace,
+    Concat,
+    ByIndex,
+}
+
+struct Merger<'a> {
+    copier: DeepCopier<'a>,
+    arrays: ArrayStrategy,
+    merging: Vec<*const c_void>,
+}
+
+impl Merger<'_> {
+    fn merge_into(&mut self, target: &LuaTable, overrides: &LuaTable) -> LuaResult<()> {
+        if self.merging.contains(&overrides.to_pointer()) {
+            return Err(mlua::Error::RuntimeError(
+                "[INSOLENCE] Error: deep_merge found a cycle in the override table".into(),
+            ));
+        }
+        self.merging.push(overrides.to_pointer());
+
+        if self.arrays != ArrayStrategy::ByIndex
+            && target.raw_len() > 0
+            && is_sequence(target)?
+            && is_sequence(overrides)?
+        {
+            if self.arrays == ArrayStrategy::Replace {
+                target.clear()?;
+            }
+
+            for value in overrides.sequence_values::<LuaValue>() {
+                let value = self.copier.copy_value(value?)?;
+                target.raw_push(value)?;
+            }
+
+            self.merging.pop();
+            return Ok(());
+        }
+
+        for pair in overrides.pairs::<LuaValue, LuaValue>() {
+            let (k, v) = pair?;
+
+            match (target.raw_get::<LuaValue>(k.clone())?, v) {
+                (LuaValue::Table(existing), LuaValue::Table(incoming)) => {
+                    self.merge_into(&existing, &incoming)?;
+                }
+                (_, v) => {
+                    let v = self.copier.copy_value(v)?;
+                    target.raw_set(k, v)?;
+                }
+            }
+        }
+
+        self.merging.pop();
+        Ok(())
+    }
+}
+
+pub(crate) fn deep_merge(
+    lua: &Lua,
+    (base, overrides, opts): (LuaTable, LuaTable, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let metatable = MetatableMode::from_opts(opts.as_ref())?;
+    let mut in_place = false;
+    let mut arrays = ArrayStrategy::Replace;
+
+    if let Some(opts) = &opts {
+        in_place = opts.get::<Option<bool>>("in_place")?.unwrap_or(false);
+        arrays = match opts.get::<Option<String>>("arrays")?.as_deref() {
+            None | Some("replace") => ArrayStrategy::Replace,
+            Some("concat") => ArrayStrategy::Concat,
+            Some("by_index") => ArrayStrategy::ByIndex,
+            Some(other) => {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "[INSOLENCE] Error: unknown array strategy {}",
+                    other
+                )));
+            }
+        };
+    }
+
+    let mut copier = DeepCopier::new(lua, metatable);
+    let target = if in_place {
+        base
+    } else {
+        copier.copy_table(&base)?
+    };
+
+    let mut merger = Merger {
+        copier,
+        arrays,
+        merging: Vec::new(),
+    };
+    merger.merge_into(&target, &overrides)?;
+
+    Ok(target)
+}
+
+struct EqualityCheck {
+    tolerance: f64,
+    metatables: bool,
+    assumed: HashSet<(*const c_void, *const c_void)>,
+}
+
+impl EqualityCheck {
+    fn values(&mut self, a: &LuaValue, b: &LuaValue) -> LuaResult<bool> {
+        if let (Some(x), Some(y)) = (as_number(a), as_number(b)) {
+            return Ok(x == y || (x - y).abs() <= self.tolerance);
+        }
+
+        match (a, b) {
+            (LuaValue::Table(x), LuaValue::Table(y)) => self.tables(x, y),
+            _ => Ok(a == b),
+        }
+    }
+
+    // Pairs already under comparison are assumed equal, which terminates on cycles.
+    fn tables(&mut self, a: &LuaTable, b: &LuaTable) -> LuaResult<bool> {
+        if a == b || !self.assumed.insert((a.to_pointer(), b.to_pointer())) {
+            return Ok(true);
+        }
+
+        if self.metatables {
+            let same_mt = match (a.metatable(), b.metatable()) {
+                (None, None) => true,
+                (Some(x), Some(y)) => self.tables(&x, &y)?,
+                _ => false,
+            };
+            if !same_mt {
+                return Ok(false);
+            }
+        }
+
+        let mut count = 0;
+        for pair in a.pairs::<LuaValue, LuaValue>() {
+            let (k, v) = pair?;
+            let other = b.raw_get::<LuaValue>(k)?;
+
+            if other.is_nil() || !self.values(&v, &other)? {
+                return Ok(false);
+            }
+            count += 1;
+        }
+
+        let mut other_count = 0;
+        for pair in b.pairs::<LuaValue, LuaValue>() {
+            pair?;
+            other_count += 1;
+        }
+
+        Ok(count == other_count)
+    }
+}
+
+pub(crate) fn values_equal(a: &LuaValue, b: &LuaValue, tolerance: f64) -> LuaResult<bool> {
+    EqualityCheck {
+        tolerance,
+        metatables: false,
+        assumed: HashSet::new(),
+    }
+    .values(a, b)
+}
+
+pub(crate) fn deep_equal(
+    _: &Lua,
+    (a, b, opts): (LuaValue, LuaValue, Option<LuaTable>),
+) -> LuaResult<bool> {
+    let mut check = EqualityCheck {
+        tolerance: 0.0,
+        metatables: false,
+        assumed: HashSet::new(),
+    };
+
+    if let Some(opts) = &opts {
+        check.tolerance = opts.get::<Option<f64>>("tolerance")?.unwrap_or(0.0);
+        check.metatables = opts.get::<Option<bool>>("metatables")?.unwrap_or(false);
+    }
+
+    check.values(&a, &b)
+}
+
+// The array part (1..n, in order) followed by the remaining hash keys. raw_len is only a border,
+// so nil holes inside it are skipped rather than handed out as entries.
+pub(crate) fn ordered_entries(tbl: &LuaTable) -> LuaResult<Vec<(LuaValue, LuaValue, bool)>> {
+    let len = tbl.raw_len();
+    let mut entries = Vec::new();
+
+    for idx in 1..=len {
+        let value: LuaValue = tbl.raw_get(idx)?;
+        if !value.is_nil() {
+            entries.push((LuaValue::Integer(idx as i64), value, true));
+        }
+    }
+
+    for pair in tbl.pairs::<LuaValue, LuaValue>() {
+        let (k, v) = pair?;
+
+        if seq_index(&k).is_some_and(|idx| idx <= len) {
+            continue;
+        }
+        entries.push((k, v, false));
+    }
+
+    Ok(entries)
+}
+
+pub(crate) enum Selector {
+    Func(LuaFunction),
+    Path(Vec<PathSegment>),
+}
+
+impl Selector {
+    pub(crate) fn from_lua(value: LuaValue) -> LuaResult<Self> {
+        match value {
+            LuaValue::Function(func) => Ok(Selector::Func(func)),
+            LuaValue::String(path) => Ok(Selector::Path(parse_path(&path.to_str()?))),
+            other => Err(mlua::Error::RuntimeError(format!(
+                "[INSOLENCE] Error: expected a function or field path, got {}",
+                other.type_name()
+            ))),
+        }
+    }
+
+    pub(crate) fn select(&self, value: &LuaValue, key: &LuaValue) -> LuaResult<LuaValue> {
+        match self {
+            Selector::Func(func) => func.call((value.clone(), key.clone())),
+            Selector::Path(path) => lookup_path(value.clone(), path),
+        }
+    }
+
+    pub(crate) fn test(&self, value: &LuaValue, key: &LuaValue) -> LuaResult<bool> {
+        Ok(!matches!(
+            self.select(value, key)?,
+            LuaValue::Nil | LuaValue::Boolean(false)
+        ))
+    }
+}
+
+pub(crate) fn map(lua: &Lua, (tbl, func): (LuaTable, LuaFunction)) -> LuaResult<LuaTable> {
+    let result = lua.create_table()?;
+
+    for (k, v, _) in ordered_entries(&tbl)? {
+        let mapped = func.call::<LuaValue>((v, k.clone()))?;
+        result.raw_set(k, mapped)?;
+    }
+
+    Ok(result)
+}
+
+fn split_by(lua: &Lua, tbl: &LuaTable, pred: &Selector) -> LuaResult<(LuaTable, LuaTable)> {
+    let kept = lua.create_table()?;
+    let rejected = lua.create_table()?;
+
+    for (k, v, in_array) in ordered_entries(tbl)? {
+        let target = if pred.test(&v, &k)? { &kept } else { &rejected };
+
+        if in_array {
+            target.raw_push(v)?;
+        } else {
+            target.raw_set(k, v)?;
+        }
+    }
+
+    Ok((kept, rejected))
+}
+
+pub(crate) fn filter(lua: &Lua, (tbl, pred): (LuaTable, LuaValue)) -> LuaResult<LuaTable> {
+    Ok(split_by(lua, &tbl, &Selector::from_lua(pred)?)?.0)
+}
+
+pub(crate) fn partition(
+    lua: &Lua,
+    (tbl, pred): (LuaTable, LuaValue),
+) -> LuaResult<(LuaTable, LuaTable)> {
+    split_by(lua, &tbl, &Selector::from_lua(pred)?)
+}
+
+pub(crate) fn reduce(
+    _: &Lua,
+    (tbl, func, init): (LuaTable, LuaFunction, Option<LuaValue>),
+) -> LuaResult<LuaValue> {
+    let mut entries = ordered_entries(&tbl)?.into_iter();
+
+    let mut acc = match init {
+        Some(init) => init,
+        None => match entries.next() {
+            Some((_, v, _)) => v,
+            None => return Ok(LuaValue::Nil),
+        },
+    };
+
+    for (k, v, _) in entries {
+        acc = func.call((acc, v, k))?;
+    }
+
+    Ok(acc)
+}
+
+pub(crate) fn find(_: &Lua, (tbl, pred): (LuaTable, LuaValue)) -> LuaResult<(LuaValue, LuaValue)> {
+    let pred = Selector::from_lua(pred)?;
+
+    for (k, v, _) in ordered_entries(&tbl)? {
+        if pred.test(&v, &k)? {
+            return Ok((v, k));
+        }
+    }
+
+    Ok((LuaValue::Nil, LuaValue::Nil))
+}
+
+pub(crate) fn any(_: &Lua, (tbl, pred): (LuaTable, LuaValue)) -> LuaResult<bool> {
+    let pred = Selector::from_lua(pred)?;
+
+    for (k, v, _) in ordered_entries(&tbl)? {
+        if pred.test(&v, &k)? {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+pub(crate) fn all(_: &Lua, (tbl, pred): (LuaTable, LuaValue)) -> LuaResult<bool> {
+    let pred = Selector::from_lua(pred)?;
+
+    for (k, v, _) in ordered_entries(&tbl)? {
+        if !pred.test(&v, &k)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+pub(crate) fn group_by(lua: &Lua, (tbl, selector): (LuaTable, LuaValue)) -> LuaResult<LuaTable> {
+    let selector = Selector::from_lua(selector)?;
+    let groups = lua.create_table()?;
+
+    for (k, v, _) in ordered_entries(&tbl)? {
+        let group_key = selector.select(&v, &k)?;
+        if group_key.is_nil() {
+            continue;
+        }
+
+        let group = match groups.raw_get::<Option<LuaTable>>(group_key.clone())? {
+            Some(group) => group,
+            None => {
+                let group = lua.create_table()?;
+                groups.raw_set(group_key, &group)?;
+                group
+            }
+        };
+        group.raw_push(v)?;
+    }
+
+    Ok(groups)
+}
+
+pub(crate) fn count_by(lua: &Lua, (tbl, selector): (LuaTable, LuaValue)) -> LuaResult<LuaTable> {
+    let selector = Selector::from_lua(selector)?;
+    let counts = lua.create_table()?;
+
+    for (k, v, _) in ordered_entries(&tbl)? {
+        let count_key = selector.select(&v, &k)?;
+        if count_key.is_nil() {
+            continue;
+        }
+
+        let count = counts
+            .raw_get::<Option<i64>>(count_key.clone())?
+            .unwrap_or(0);
+        counts.raw_set(count_key, count + 1)?;
+    }
+
+    Ok(counts)
+}
+
+pub(crate) fn index_by(lua: &Lua, (tbl, selector): (LuaTable, LuaValue)) -> LuaResult<LuaTable> {
+    let selector = Selector::from_lua(selector)?;
+    let index = lua.create_table()?;
+
+    for (k, v, _) in ordered_entries(&tbl)? {
+        let index_key = selector.select(&v, &k)?;
+        if !index_key.is_nil() {
+            index.raw_set(index_key, v)?;
+        }
+    }
+
+    Ok(index)
+}
+
+pub(crate) fn zip(lua: &Lua, tbls: LuaVariadic<LuaTable>) -> LuaResult<LuaTable> {
+    let zipped = lua.create_table()?;
+    let len = tbls.iter().map(|tbl| tbl.raw_len()).min().unwrap_or(0);
+
+    for idx in 1..=len {
+        let tuple = lua.create_table()?;
+        for tbl in tbls.iter() {
+            tuple.raw_push(tbl.raw_get::<LuaValue>(idx)?)?;
+        }
+        zipped.raw_push(tuple)?;
+    }
+
+    Ok(zipped)
+}
+
+pub(crate) fn flat_map(lua: &Lua, (tbl, func): (LuaTable, LuaFunction)) -> LuaResult<LuaTable> {
+    let result = lua.create_table()?;
+
+    for (k, v, _) in ordered_entries(&tbl)? {
+        match func.call::<LuaValue>((v, k))? {
+            LuaValue::Table(items) => {
+                for item in items.sequence_values::<LuaValue>() {
+                    result.raw_push(item?)?;
+                }
+            }
+            LuaValue::Nil => {}
+            other => result.raw_push(other)?,
+        }
+    }
+
+    Ok(result)
+}
+
+struct SortKey {
+    path: Vec<PathSegment>,
+    descending: bool,
+    nils_first: bool,
+}
+
+enum SortSpec {
+    Keys(Vec<SortKey>),
+    Comparator(LuaFunction),
+}
+
+impl SortKey {
+    fn from_lua(value: LuaValue) -> LuaResult<Self> {
+        match value {
+            LuaValue::String(path) => Ok(SortKey {
+                path: parse_path(&path.to_str()?),
+                descending: false,
+                nils_first: false,
+            }),
+            LuaValue::Table(spec) => Ok(SortKey {
+                path: parse_path(&spec.get::<String>("path")?),
+                descending: spec.get::<Option<bool>>("desc")?.unwrap_or(false),
+                nils_first: spec.get::<Option<String>>("nils")?.as_deref() == Some("first"),
+            }),
+            other => Err(mlua::Error::RuntimeError(format!(
+                "[INSOLENCE] Error: expected a sort key path or spec, got {}",
+                other.type_name()
+            ))),
+        }
+    }
+}
+
+impl SortSpec {
+    fn from_lua(value: LuaValue) -> LuaResult<Self> {
+        match value {
+            LuaValue::Function(func) => Ok(SortSpec::Comparator(func)),
+            LuaValue::Table(keys) => Ok(SortSpec::Keys(
+                keys.sequence_values::<LuaValue>()
+                    .map(|key| SortKey::from_lua(key?))
+                    .collect::<LuaResult<_>>()?,
+            )),
+            other => Ok(SortSpec::Keys(vec![SortKey::from_lua(other)?])),
+        }
+    }
+}
+
+fn type_order(value: &LuaValue) -> u8 {
+    match value {
+        LuaValue::Boolean(_) => 0,
+        LuaValue::Integer(_) | LuaValue::Number(_) => 1,
+        LuaValue::String(_) => 2,
+        _ => 3,
+    }
+}
+
+// Orders two non-nil values: numbers numerically, strings bytewise, then by type.
+pub(crate) fn compare_values(a: &LuaValue, b: &LuaValue) -> Ordering {
+    if let (Some(x), Some(y)) = (as_number(a), as_number(b)) {
+        return x.total_cmp(&y);
+    }
+
+    match (a, b) {
+        (LuaValue::String(x), LuaValue::String(y)) => x.as_bytes().cmp(&y.as_bytes()),
+        (LuaValue::Boolean(x), LuaValue::Boolean(y)) => x.cmp(y),
+        _ => type_order(a).cmp(&type_order(b)),
+    }
+}
+
+// A plain merge sort: stable, and unlike `slice::sort_by` it tolerates Lua comparators that are
+// not a consistent total order instead of panicking.
+pub(crate) fn stable_sort_by<T>(items: Vec<T>, mut cmp: impl FnMut(&T, &T) -> Ordering) -> Vec<T> {
+    let mut runs: Vec<Vec<T>> = items.into_iter().map(|item| vec![item]).collect();
+
+    while runs.len() > 1 {
+        let mut merged_runs = Vec::with_capacity(runs.len().div_ceil(2));
+        let mut pending = runs.into_iter();
+
+        while let Some(left) = pending.next() {
+            let Some(right) = pending.next() else {
+                merged_runs.push(left);
+                break;
+            };
+
+            let mut merged = Vec::with_capacity(left.len() + right.len());
+            let mut left = left.into_iter().peekable();
+            let mut right = right.into_iter().peekable();
+
+            while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+                if cmp(r, l) == Ordering::Less {
+                    merged.extend(right.next());
+                } else {
+                    merged.extend(left.next());
+                }
+            }
+            merged.extend(left);
+            merged.extend(right);
+            merged_runs.push(merged);
+        }
+
+        runs = merged_runs;
+    }
+
+    runs.pop().unwrap_or_default()
+}
+
+fn sorted_values(tbl: &LuaTable, spec: LuaValue) -> LuaResult<Vec<LuaValue>> {
+    let spec = SortSpec::from_lua(spec)?;
+    let values: Vec<LuaValue> = tbl
+        .sequence_values::<LuaValue>()
+        .collect::<LuaResult<_>>()?;
+    let mut failure: Option<mlua::Error> = None;
+
+    let keyed: Vec<(Vec<LuaValue>, LuaValue)> = match &spec {
+        SortSpec::Keys(keys) => values
+            .into_iter()
+            .map(|value| {
+                let fields = keys
+                    .iter()
+                    .map(|key| lookup_path(value.clone(), &key.path))
+                    .collect::<LuaResult<Vec<LuaValue>>>()?;
+                Ok((fields, value))
+            })
+            .collect::<LuaResult<_>>()?,
+        SortSpec::Comparator(_) => values.into_iter().map(|v| (Vec::new(), v)).collect(),
+    };
+
+    let keyed = stable_sort_by(keyed, |(a_fields, a), (b_fields, b)| match &spec {
+        SortSpec::Comparator(less) => {
+            if failure.is_some() {
+                return Ordering::Equal;
+            }
+
+            let mut is_less = |x: &LuaValue, y: &LuaValue| match less.call::<bool>((x, y)) {
+                Ok(result) => result,
+                Err(err) => {
+                    failure = Some(err);
+                    false
+                }
+            };
+
+            if is_less(a, b) {
+                Ordering::Less
+            } else if is_less(b, a) {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        }
+        SortSpec::Keys(keys) => {
+            for (idx, key) in keys.iter().enumerate() {
+                let order = match (&a_fields[idx], &b_fields[idx]) {
+                    (LuaValue::Nil, LuaValue::Nil) => Ordering::Equal,
+                    (LuaValue::Nil, _) if key.nils_first => Ordering::Less,
+                    (LuaValue::Nil, _) => Ordering::Greater,
+                    (_, LuaValue::Nil) if key.nils_first => Ordering::Greater,
+                    (_, LuaValue::Nil) => Ordering::Less,
+                    (x, y) if key.descending => compare_values(y, x),
+                    (x, y) => compare_values(x, y),
+                };
+
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+
+            Ordering::Equal
+        }
+    });
+
+    if let Some(err) = failure {
+        return Err(err);
+    }
+
+    Ok(keyed.into_iter().map(|(_, value)| value).collect())
+}
+
+pub(crate) fn sort_by(lua: &Lua, (tbl, keys): (LuaTable, LuaValue)) -> LuaResult<LuaTable> {
+    lua.create_sequence_from(sorted_values(&tbl, keys)?)
+}
+
+pub(crate) fn sort_by_in_place(_: &Lua, (tbl, keys): (LuaTable, LuaValue)) -> LuaResult<LuaTable> {
+    for (idx, value) in sorted_values(&tbl, keys)?.into_iter().enumerate() {
+        tbl.raw_set(idx + 1, value)?;
+    }
+
+    Ok(tbl)
+}
+
+struct Candidate {
+    key: LuaValue,
+    value: LuaValue,
+    score: LuaValue,
+}
+
+struct Ranker {
+    by: Option<Selector>,
+    compare: Option<LuaFunction>,
+    descending: bool,
+}
+
+impl Ranker {
+    fn new(by: LuaValue, opts: Option<&LuaTable>, descending: bool) -> LuaResult<Self> {
+        let mut ranker = Ranker {
+            by: match by {
+                LuaValue::Nil => None,
+                other => Some(Selector::from_lua(other)?),
+            },
+            compare: None,
+            descending,
+        };
+
+        if let Some(opts) = opts {
+            ranker.compare = opts.get::<Option<LuaFunction>>("compare")?;
+            ranker.descending = match opts.get::<Option<String>>("order")?.as_deref() {
+                None => descending,
+                Some("desc") => true,
+                Some("asc") => false,
+                Some(other) => {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        "[INSOLENCE] Error: unknown order {}",
+                        other
+                    )));
+                }
+            };
+        }
+
+        Ok(ranker)
+    }
+
+    // Array entries first, then the remaining keys in sorted order, so ties resolve the same way
+    // on every run. Entries without a numeric score are skipped unless a comparator is given.
+    fn candidates(&self, tbl: &LuaTable) -> LuaResult<Vec<Candidate>> {
+        let mut entries = ordered_entries(tbl)?;
+        let split = entries
+            .iter()
+            .take_while(|(_, _, in_array)| *in_array)
+            .count();
+        let hash = entries.split_off(split);
+        entries.extend(stable_sort_by(hash, |(a, _, _), (b, _, _)| {
+            compare_values(a, b)
+        }));
+
+        let mut candidates = Vec::new();
+        for (key, value, _) in entries {
+            let score = match &self.by {
+                Some(by) => by.select(&value, &key)?,
+                None => value.clone(),
+            };
+
+            if self.compare.is_some() || as_number(&score).is_some() {
+                candidates.push(Candidate { key, value, score });
+            }
+        }
+
+        Ok(candidates)
+    }
+
+    // `Less` means `a` ranks ahead of `b`.
+    fn order(&self, a: &Candidate, b: &Candidate) -> LuaResult<Ordering> {
+        if let Some(compare) = &self.compare {
+            let ahead = |x: &Candidate, y: &Candidate| -> LuaResult<bool> {
+                compare.call((x.score.clone(), y.score.clone()))
+            };
+
+            return Ok(if ahead(a, b)? {
+                Ordering::Less
+            } else if ahead(b, a)? {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            });
+        }
+
+        let order = compare_values(&a.score, &b.score);
+        Ok(if self.descending {
+            order.reverse()
+        } else {
+            order
+        })
+    }
+
+    fn sorted(&self, tbl: &LuaTable) -> LuaResult<Vec<Candidate>> {
+        let mut failure: Option<mlua::Error> = None;
+
+        let sorted = stable_sort_by(self.candidates(tbl)?, |a, b| {
+            if failure.is_some() {
+                return Ordering::Equal;
+            }
+
+            self.order(a, b).unwrap_or_else(|err| {
+                failure = Some(err);
+                Ordering::Equal
+            })
+        });
+
+        match failure {
+            Some(err) => Err(err),
+            None => Ok(sorted),
+        }
+    }
+}
+
+fn ties_option(opts: Option<&LuaTable>, default: &str, allowed: &[&str]) -> LuaResult<String> {
+    let ties = match opts {
+        Some(opts) => opts.get::<Option<String>>("ties")?,
+        None => None,
+    }
+    .unwrap_or_else(|| default.to_string());
+
+    if !allowed.contains(&ties.as_str()) {
+        return Err(mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: unknown tie policy {} (expected {})",
+            ties,
+            allowed.join(", ")
+        )));
+    }
+
+    Ok(ties)
+}
+
+fn best_by(
+    tbl: &LuaTable,
+    by: LuaValue,
+    opts: Option<&LuaTable>,
+    descending: bool,
+) -> LuaResult<(LuaValue, LuaValue, LuaValue)> {
+    let ranker = Ranker::new(by, opts, descending)?;
+    let ties = ties_option(opts, "first", &["first", "last"])?;
+    let sorted = ranker.sorted(tbl)?;
+
+    let Some(first) = sorted.first() else {
+        return Ok((LuaValue::Nil, LuaValue::Nil, LuaValue::Nil));
+    };
+
+    let mut best = first;
+    if ties == "last" {
+        for candidate in &sorted[1..] {
+            if ranker.order(first, candidate)? != Ordering::Equal {
+                break;
+            }
+            best = candidate;
+        }
+    }
+
+    Ok((best.key.clone(), best.value.clone(), best.score.clone()))
+}
+
+pub(crate) fn argmax_by(
+    _: &Lua,
+    (tbl, by, opts): (LuaTable, LuaValue, Option<LuaTable>),
+) -> LuaResult<(LuaValue, LuaValue, LuaValue)> {
+    best_by(&tbl, by, opts.as_ref(), true)
+}
+
+pub(crate) fn argmin_by(
+    _: &Lua,
+    (tbl, by, opts): (LuaTable, LuaValue, Option<LuaTable>),
+) -> LuaResult<(LuaValue, LuaValue, LuaValue)> {
+    best_by(&tbl, by, opts.as_ref(), false)
+}
+
+pub(crate) fn top_k(
+    lua: &Lua,
+    (tbl, k, opts): (LuaTable, usize, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let by = match &opts {
+        Some(opts) => opts.get::<LuaValue>("by")?,
+        None => LuaValue::Nil,
+    };
+    let ranker = Ranker::new(by, opts.as_ref(), true)?;
+    let ties = ties_option(opts.as_ref(), "first", &["first", "include"])?;
+    let sorted = ranker.sorted(&tbl)?;
+
+    let mut count = k.min(sorted.len());
+    if ties == "include" && count > 0 {
+        while count < sorted.len()
+            && ranker.order(&sorted[count - 1], &sorted[count])? == Ordering::Equal
+        {
+            count += 1;
+        }
+    }
+
+    let result = lua.create_table()?;
+    for candidate in sorted.into_iter().take(count) {
+        let entry = lua.create_table()?;
+        entry.set("key", candidate.key)?;
+        entry.set("value", candidate.value)?;
+        entry.set("score", candidate.score)?;
+        result.raw_push(entry)?;
+    }
+
+    Ok(result)
+}
+
+pub(crate) fn rank(lua: &Lua, (tbl, opts): (LuaTable, Option<LuaTable>)) -> LuaResult<LuaTable> {
+    let by = match &opts {
+        Some(opts) => opts.get::<LuaValue>("by")?,
+        None => LuaValue::Nil,
+    };
+    let ranker = Ranker::new(by, opts.as_ref(), true)?;
+    let ties = ties_option(
+        opts.as_ref(),
+        "competition",
+        &["competition", "dense", "ordinal", "average"],
+    )?;
+    let sorted = ranker.sorted(&tbl)?;
+    let result = lua.create_table()?;
+
+    let mut start = 0;
+    let mut dense = 0;
+    while start < sorted.len() {
+        let mut end = start + 1;
+        while end < sorted.len() && ranker.order(&sorted[start], &sorted[end])? == Ordering::Equal {
+            end += 1;
+        }
+        dense += 1;
+
+        for (offset, candidate) in sorted[start..end].iter().enumerate() {
+            let position = match ties.as_str() {
+                "dense" => dense as f64,
+                "ordinal" => (start + offset + 1) as f64,
+                "average" => (start + end + 1) as f64 / 2.0,
+                _ => (start + 1) as f64,
+            };
+            result.raw_set(candidate.key.clone(), position)?;
+        }
+
+        start = end;
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sparse(lua: &Lua) -> LuaTable {
+        lua.load("return {[1] = 1, [2] = 2, [4] = 4}")
+            .eval()
+            .unwrap()
+    }
+
+    #[test]
+    fn map_skips_holes() {
+        let lua = Lua::new();
+        let double = lua
+            .create_function(|_, (v, _): (i64, LuaValue)| Ok(v * 2))
+            .unwrap();
+        let mapped = map(&lua, (sparse(&lua), double)).unwrap();
+
+        assert_eq!(mapped.raw_get::<Option<i64>>(1).unwrap(), Some(2));
+        assert_eq!(mapped.raw_get::<Option<i64>>(2).unwrap(), Some(4));
+        assert_eq!(mapped.raw_get::<Option<i64>>(3).unwrap(), None);
+        assert_eq!(mapped.raw_get::<Option<i64>>(4).unwrap(), Some(8));
+    }
+
+    #[test]
+    fn filter_skips_holes() {
+        let lua = Lua::new();
+        let even = lua.create_function(|_, v: i64| Ok(v % 2 == 0)).unwrap();
+        let kept = filter(&lua, (sparse(&lua), LuaValue::Function(even))).unwrap();
+
+        let values: Vec<i64> = kept
+            .pairs::<LuaValue, i64>()
+            .map(|p| p.unwrap().1)
+            .collect();
+        assert_eq!(values.len(), 2);
+        assert!(values.contains(&2) && values.contains(&4));
+    }
+
+    #[test]
+    fn extreme_values_of_an_integer_array() {
+        let lua = Lua::new();
+        let values: LuaTable = lua.load("return {3, 1, 2}").eval().unwrap();
+
+        let largest = largest_val(&lua, values.clone()).unwrap();
+        let smallest = smallest_val(&lua, values).unwrap();
+        assert_eq!(largest.and_then(|k| k.as_i64()), Some(1));
+        assert_eq!(smallest.and_then(|k| k.as_i64()), Some(2));
+    }
+
+    #[test]
+    fn reduce_skips_holes() {
+        let lua = Lua::new();
+        let add = lua
+            .create_function(|_, (acc, v): (i64, i64)| Ok(acc + v))
+            .unwrap();
+
+        let total = reduce(&lua, (sparse(&lua), add.clone(), None)).unwrap();
+        assert_eq!(total.as_i64(), Some(7));
+
+        let seeded = reduce(&lua, (sparse(&lua), add, Some(LuaValue::Integer(10)))).unwrap();
+        assert_eq!(seeded.as_i64(), Some(17));
+    }
+}
@@ -1,17 +1,379 @@
 use mlua::prelude::*;
+use unicode_segmentation::UnicodeSegmentation;
 
-pub(crate) fn capitalize(lua: &Lua, word: Option<String>) -> LuaResult<LuaValue> {
+const SMALL_WORDS: [&str; 17] = [
+    "a", "an", "and", "as", "at", "but", "by", "for", "in", "nor", "of", "on", "or", "the", "to",
+    "up", "via",
+];
+
+fn is_apostrophe(grapheme: &str) -> bool {
+    grapheme == "'" || grapheme == "\u{2019}"
+}
+
+fn first_char(grapheme: &str) -> char {
+    grapheme.chars().next().unwrap_or(' ')
+}
+
+// Uppercases the first grapheme; the rest is lowercased unless `keep_rest` is set.
+fn capitalize_str(word: &str, keep_rest: bool) -> String {
+    let mut graphemes = word.graphemes(true);
+    let Some(first) = graphemes.next() else {
+        return String::new();
+    };
+    let rest = graphemes.as_str();
+
+    if keep_rest {
+        format!("{}{}", first.to_uppercase(), rest)
+    } else {
+        format!("{}{}", first.to_uppercase(), rest.to_lowercase())
+    }
+}
+
+// Splits identifiers and prose into words: on anything that is not a letter or digit, and on
+// case changes ("jokerOfDoom", "HTTPServer"). Apostrophes inside a word are dropped.
+fn split_words(text: &str) -> Vec<String> {
+    let graphemes: Vec<&str> = text.graphemes(true).collect();
+    let mut words = Vec::new();
+    let mut current = String::new();
+
+    for (idx, grapheme) in graphemes.iter().enumerate() {
+        let ch = first_char(grapheme);
+
+        if !ch.is_alphanumeric() {
+            let inside_word = is_apostrophe(grapheme)
+                && !current.is_empty()
+                && graphemes
+                    .get(idx + 1)
+                    .is_some_and(|next| first_char(next).is_alphanumeric());
+
+            if !inside_word && !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+
+        if ch.is_uppercase() && !current.is_empty() {
+            let prev = idx.checked_sub(1).map(|p| first_char(graphemes[p]));
+            let next = graphemes.get(idx + 1).map(|next| first_char(next));
+
+            let after_lower = prev.is_some_and(|p| p.is_lowercase() || p.is_numeric());
+            let ends_acronym =
+                prev.is_some_and(char::is_uppercase) && next.is_some_and(char::is_lowercase);
+
+            if after_lower || ends_acronym {
+                words.push(std::mem::take(&mut current));
+            }
+        }
+
+        current.push_str(grapheme);
+    }
+
+    if !current.is_empty() {
+        words.push(current);
+    }
+
+    words
+}
+
+fn keep_rest_opt(opts: Option<&LuaTable>) -> LuaResult<bool> {
+    match opts {
+        Some(opts) => Ok(opts.get::<Option<bool>>("keep_rest")?.unwrap_or(false)),
+        None => Ok(false),
+    }
+}
+
+pub(crate) fn capitalize(
+    lua: &Lua,
+    (word, opts): (Option<String>, Option<LuaTable>),
+) -> LuaResult<LuaValue> {
     match word {
         None => Ok(LuaValue::Nil),
-        Some(word) if word.is_empty() => Ok(LuaValue::String(lua.create_string("")?)),
-        Some(word) => {
-            let mut chars = word.chars();
-            let first_char = chars.next().unwrap().to_uppercase().collect::<String>();
-            let rest = chars.collect::<String>().to_lowercase();
+        Some(word) => Ok(LuaValue::String(
+            lua.create_string(capitalize_str(&word, keep_rest_opt(opts.as_ref())?))?,
+        )),
+    }
+}
+
+pub(crate) fn title_case(_: &Lua, (text, opts): (String, Option<LuaTable>)) -> LuaResult<String> {
+    let keep_rest = keep_rest_opt(opts.as_ref())?;
+    let small_words: Vec<String> = match &opts {
+        Some(opts) => match opts.get::<Option<LuaTable>>("small_words")? {
+            Some(words) => words
+                .sequence_values::<String>()
+                .map(|word| word.map(|word| word.to_lowercase()))
+                .collect::<LuaResult<_>>()?,
+            None => SMALL_WORDS.iter().map(|word| word.to_string()).collect(),
+        },
+        None => SMALL_WORDS.iter().map(|word| word.to_string()).collect(),
+    };
+
+    let words: Vec<&str> = text.split_word_bounds().collect();
+    let first = words
+        .iter()
+        .position(|word| first_char(word).is_alphanumeric());
+    let last = words
+        .iter()
+        .rposition(|word| first_char(word).is_alphanumeric());
+
+    let mut out = String::with_capacity(text.len());
+    for (idx, word) in words.iter().enumerate() {
+        if !first_char(word).is_alphanumeric() {
+            out.push_str(word);
+        } else if Some(idx) != first
+            && Some(idx) != last
+            && small_words.contains(&word.to_lowercase())
+        {
+            out.push_str(&word.to_lowercase());
+        } else {
+            out.push_str(&capitalize_str(word, keep_rest));
+        }
+    }
+
+    Ok(out)
+}
+
+// Lowercases everything except the first letter of each sentence.
+pub(crate) fn sentence_case(_: &Lua, text: String) -> LuaResult<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut sentence_start = true;
+
+    for grapheme in text.graphemes(true) {
+        let ch = first_char(grapheme);
+
+        if sentence_start && ch.is_alphanumeric() {
+            out.push_str(&grapheme.to_uppercase());
+            sentence_start = false;
+        } else {
+            out.push_str(&grapheme.to_lowercase());
+            if matches!(ch, '.' | '!' | '?') {
+                sentence_start = true;
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+pub(crate) fn snake_case(_: &Lua, text: String) -> LuaResult<String> {
+    Ok(split_words(&text)
+        .iter()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("_"))
+}
+
+pub(crate) fn kebab_case(_: &Lua, text: String) -> LuaResult<String> {
+    Ok(split_words(&text)
+        .iter()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("-"))
+}
+
+pub(crate) fn camel_case(_: &Lua, (text, opts): (String, Option<LuaTable>)) -> LuaResult<String> {
+    let pascal = match &opts {
+        Some(opts) => opts.get::<Option<bool>>("pascal")?.unwrap_or(false),
+        None => false,
+    };
+
+    Ok(split_words(&text)
+        .iter()
+        .enumerate()
+        .map(|(idx, word)| {
+            if idx == 0 && !pascal {
+                word.to_lowercase()
+            } else {
+                capitalize_str(word, false)
+            }
+        })
+        .collect())
+}
+
+// Optimal string alignment distance: Levenshtein plus adjacent transpositions when `transpose`.
+fn edit_distance_chars(a: &[char], b: &[char], transpose: bool) -> usize {
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+
+            if transpose && i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+
+    rows[a.len()][b.len()]
+}
+
+fn similarity(a: &[char], b: &[char]) -> f64 {
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    1.0 - edit_distance_chars(a, b, true) as f64 / longest as f64
+}
+
+// Greedy in-order match of every query char; rewards runs and hits at word starts.
+fn subsequence_score(query: &[char], candidate: &[char]) -> Option<f64> {
+    let mut hits = 0;
+    let mut last: Option<usize> = None;
+    let mut from = 0;
+
+    for &ch in query {
+        let offset = candidate[from..].iter().position(|&c| c == ch)?;
+        let at = from + offset;
+
+        let word_start = at == 0 || !candidate[at - 1].is_alphanumeric();
+        if word_start || last.is_some_and(|last| last + 1 == at) {
+            hits += 1;
+        }
+        last = Some(at);
+        from = at + 1;
+    }
+
+    Some(hits as f64 / query.len() as f64)
+}
+
+// Scores how well `query` matches `candidate` from 0 to 1, case-insensitively. Exact matches score
+// 1, then prefixes, word prefixes, substrings, subsequences ("gjk" for "Green Joker") and finally
+// near misses by edit distance. None means no match at all.
+pub(crate) fn fuzzy_score(query: &str, candidate: &str) -> Option<f64> {
+    let query = query.trim().to_lowercase();
+    let lowered = candidate.to_lowercase();
+    if query.is_empty() || lowered.is_empty() {
+        return None;
+    }
+
+    let q: Vec<char> = query.chars().collect();
+    let c: Vec<char> = lowered.chars().collect();
+    let coverage = q.len() as f64 / c.len().max(q.len()) as f64;
+
+    if lowered == query {
+        return Some(1.0);
+    }
+    if lowered.starts_with(&query) {
+        return Some(0.85 + 0.1 * coverage);
+    }
+    if split_words(candidate)
+        .iter()
+        .any(|word| word.to_lowercase().starts_with(&query))
+    {
+        return Some(0.75 + 0.1 * coverage);
+    }
+    if lowered.contains(&query) {
+        return Some(0.65 + 0.1 * coverage);
+    }
+    if let Some(hits) = subsequence_score(&q, &c) {
+        return Some(0.3 + 0.3 * hits);
+    }
+
+    // Typos: compare against the whole candidate, each word, and the prefix of the same length.
+    let mut best = similarity(&q, &c);
+    for word in split_words(candidate) {
+        let word: Vec<char> = word.to_lowercase().chars().collect();
+        best = best.max(similarity(&q, &word));
+    }
+    if c.len() > q.len() {
+        best = best.max(similarity(&q, &c[..q.len()]));
+    }
+
+    if best >= 0.6 { Some(best * 0.6) } else { None }
+}
+
+pub(crate) fn edit_distance(
+    _: &Lua,
+    (a, b, opts): (String, String, Option<LuaTable>),
+) -> LuaResult<usize> {
+    let (transpose, case_sensitive) = match &opts {
+        Some(opts) => (
+            opts.get::<Option<bool>>("transpositions")?.unwrap_or(true),
+            opts.get::<Option<bool>>("case_sensitive")?.unwrap_or(true),
+        ),
+        None => (true, true),
+    };
+
+    let chars = |s: &str| -> Vec<char> {
+        if case_sensitive {
+            s.chars().collect()
+        } else {
+            s.to_lowercase().chars().collect()
+        }
+    };
+
+    Ok(edit_distance_chars(&chars(&a), &chars(&b), transpose))
+}
+
+pub(crate) fn fuzzy_match(_: &Lua, (query, candidate): (String, String)) -> LuaResult<Option<f64>> {
+    Ok(fuzzy_score(&query, &candidate))
+}
+
+pub(crate) struct SearchOpts {
+    pub(crate) limit: Option<usize>,
+    pub(crate) threshold: f64,
+}
+
+impl SearchOpts {
+    pub(crate) fn from_opts(opts: Option<&LuaTable>) -> LuaResult<Self> {
+        let Some(opts) = opts else {
+            return Ok(SearchOpts {
+                limit: Some(10),
+                threshold: 0.0,
+            });
+        };
 
-            Ok(LuaValue::String(
-                lua.create_string(format!("{}{}", first_char, rest))?,
-            ))
+        // limit = 0 returns every match.
+        let limit = opts.get::<Option<usize>>("limit")?.unwrap_or(10);
+        Ok(SearchOpts {
+            limit: if limit == 0 { None } else { Some(limit) },
+            threshold: opts.get::<Option<f64>>("threshold")?.unwrap_or(0.0),
+        })
+    }
+
+    // Best scores first, ties broken by text so results are stable between runs.
+    pub(crate) fn rank<T>(&self, mut results: Vec<(f64, String, T)>) -> Vec<(f64, String, T)> {
+        results.retain(|(score, _, _)| *score >= self.threshold);
+        results.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        if let Some(limit) = self.limit {
+            results.truncate(limit);
         }
+
+        results
     }
 }
+
+pub(crate) fn fuzzy_search(
+    lua: &Lua,
+    (query, candidates, opts): (String, Vec<String>, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let search = SearchOpts::from_opts(opts.as_ref())?;
+
+    let scored = candidates
+        .into_iter()
+        .enumerate()
+        .filter_map(|(idx, text)| fuzzy_score(&query, &text).map(|score| (score, text, idx + 1)))
+        .collect();
+
+    let result = lua.create_table()?;
+    for (score, text, index) in search.rank(scored) {
+        let entry = lua.create_table()?;
+        entry.set("text", text)?;
+        entry.set("index", index)?;
+        entry.set("score", score)?;
+        result.raw_push(entry)?;
+    }
+
+    Ok(result)
+}
@@ -1,3 +1,5 @@
+use crate::tbl::{PathSegment, lookup_path, parse_path, query_path};
+use crate::text::{SearchOpts, fuzzy_score};
 use mlua::prelude::*;
 
 pub(crate) fn placeholder_sprite(lua: &Lua, _: ()) -> LuaResult<LuaTable> {
@@ -12,17 +14,13 @@ pub(crate) fn placeholder_sprite(lua: &Lua, _: ()) -> LuaResult<LuaTable> {
 pub(crate) fn boobs_sprite(lua: &Lua, mod_cfg: LuaValue) -> LuaResult<LuaTable> {
     let sprite_tbl = lua.create_table()?;
 
-    if let LuaValue::Table(modcfg) = mod_cfg {
-        let adult_mode = modcfg
-            .get::<LuaTable>("config")?
-            .get::<LuaValue>("adult_mode")?
-            .as_boolean()
-            .unwrap();
+    if let LuaValue::Table(_) = mod_cfg {
+        let adult_mode = matches!(
+            lookup_path(mod_cfg, &parse_path("config.adult_mode"))?,
+            LuaValue::Boolean(true)
+        );
 
-        if !adult_mode {
-            sprite_tbl.set("x", 1000)?;
-            sprite_tbl.set("y", 1000)?;
-        } else if adult_mode {
+        if adult_mode {
             sprite_tbl.set("x", 12)?;
             sprite_tbl.set("y", 2)?;
         } else {
@@ -78,68 +76,58 @@ pub(crate) fn word_to_color(_: &Lua, word: String) -> LuaResult<String> {
 }
 
 pub(crate) fn is_rigged_cryptid(lua: &Lua, card: LuaTable) -> LuaResult<bool> {
-    let can_load = lua
-        .globals()
-        .get::<Option<LuaTable>>("SMODS")?
-        .and_then(|smods| smods.get::<Option<LuaTable>>("Mods").ok().flatten())
-        .and_then(|mods| mods.get::<Option<LuaTable>>("Cryptid").ok().flatten())
-        .and_then(|mods| mods.get::<Option<bool>>("can_load").ok().flatten())
-        .unwrap_or(false);
-
-    if !can_load {
+    let can_load = lookup_path(
+        LuaValue::Table(lua.globals()),
+        &parse_path("SMODS.Mods.Cryptid.can_load"),
+    )?;
+
+    // Converting to bool follows Lua truthiness: anything but nil and false counts.
+    if !lua.unpack::<bool>(can_load)? {
         return Ok(false);
     }
 
-    let cry_rigged = card
-        .get::<Option<LuaTable>>("ability")?
-        .and_then(|ability| ability.get::<Option<bool>>("cry_rigged").ok().flatten())
-        .unwrap_or(false);
+    let cry_rigged = lookup_path(LuaValue::Table(card), &parse_path("ability.cry_rigged"))?;
 
-    Ok(cry_rigged)
+    lua.unpack::<bool>(cry_rigged)
 }
 
 pub(crate) fn mod_cond(
     lua: &Lua,
     (mod_id, if_exists, otherwise): (String, LuaValue, LuaValue),
 ) -> LuaResult<LuaValue> {
-    let can_load = lua
-        .globals()
-        .get::<Option<LuaTable>>("SMODS")?
-        .and_then(|smods| smods.get::<Option<LuaTable>>("Mods").ok().flatten())
-        .and_then(|mods| mods.get::<Option<LuaTable>>(mod_id).ok().flatten())
-        .and_then(|mod_entry| mod_entry.get::<Option<bool>>("can_load").ok().flatten())
-        .unwrap_or(false);
-
-    Ok(if can_load { if_exists } else { otherwise })
+    let can_load = lookup_path(
+        LuaValue::Table(lua.globals()),
+        &[
+            PathSegment::Key("SMODS".into()),
+            PathSegment::Key("Mods".into()),
+            PathSegment::Key(mod_id),
+            PathSegment::Key("can_load".into()),
+        ],
+    )?;
+
+    Ok(if lua.unpack::<bool>(can_load)? {
+        if_exists
+    } else {
+        otherwise
+    })
 }
 
 pub(crate) fn count_num_of_joker(lua: &Lua, (prefix, key): (String, String)) -> LuaResult<u32> {
-    let mut num_of_joker = 0;
     let target_name = format!("j_{}_{}", prefix, key);
-    let cards = lua
-        .globals()
-        .get::<Option<LuaTable>>("G")?
-        .and_then(|g| g.get::<Option<LuaTable>>("jokers").ok().flatten())
-        .and_then(|jokers| jokers.get::<Option<LuaTable>>("cards").ok().flatten());
-
-    if let Some(card_table) = cards {
-        for pair in card_table.pairs::<LuaValue, LuaTable>().flatten() {
-            let (_, card) = pair;
-            let name_matches = card
-                .get::<Option<LuaTable>>("ability")
-                .ok()
-                .flatten()
-                .and_then(|ability| ability.get::<Option<String>>("name").ok().flatten())
-                .map(|name| name == target_name)
-                .unwrap_or(false);
-
-            if name_matches {
-                num_of_joker += 1;
-            }
-        }
-    }
-
-    Ok(num_of_joker)
+    let names = query_path(
+        LuaValue::Table(lua.globals()),
+        &parse_path("G.jokers.cards.*.ability.name"),
+    )?;
+
+    let num_of_joker = names
+        .iter()
+        .filter(|(_, name)| match name {
+            LuaValue::String(name) => name.to_str().is_ok_and(|name| *name == target_name),
+            _ => false,
+        })
+        .count();
+
+    Ok(num_of_joker as u32)
 }
 
 pub(crate) fn register_items(lua: &Lua, (items, path): (Vec<String>, String)) -> LuaResult<()> {
@@ -283,6 +271,124 @@ pub(crate) fn animate_center(
     Ok(())
 }
 
+fn center_name(localization: Option<&LuaTable>, set: &str, key: &str) -> LuaResult<Option<String>> {
+    let Some(localization) = localization else {
+        return Ok(None);
+    };
+
+    let path =
+        ["descriptions", set, key, "name"].map(|segment| PathSegment::Key(segment.to_string()));
+    match lookup_path(LuaValue::Table(localization.clone()), &path)? {
+        LuaValue::String(name) => Ok(Some(name.to_str()?.to_string())),
+        LuaValue::Table(lines) => Ok(Some(
+            lines
+                .sequence_values::<String>()
+                .collect::<LuaResult<Vec<_>>>()?
+                .join(" "),
+        )),
+        _ => Ok(None),
+    }
+}
+
+fn set_filter(value: LuaValue) -> LuaResult<Option<Vec<String>>> {
+    match value {
+        LuaValue::Nil => Ok(None),
+        LuaValue::String(set) => Ok(Some(vec![set.to_str()?.to_lowercase()])),
+        LuaValue::Table(sets) => Ok(Some(
+            sets.sequence_values::<String>()
+                .map(|set| set.map(|set| set.to_lowercase()))
+                .collect::<LuaResult<_>>()?,
+        )),
+        other => Err(mlua::Error::RuntimeError(format!(
+            "[INSOLENCE] Error: expected a set name or list of set names, got {}",
+            other.type_name()
+        ))),
+    }
+}
+
+// Ranks G.P_CENTERS against `query` by localized name, key, and key without its class prefix
+// ("j_greedy_joker" also matches as "greedy joker"). `opts.centers` and `opts.localization`
+// stand in for the game tables.
+pub(crate) fn search_centers(
+    lua: &Lua,
+    (query, opts): (String, Option<LuaTable>),
+) -> LuaResult<LuaTable> {
+    let search = SearchOpts::from_opts(opts.as_ref())?;
+    let globals = LuaValue::Table(lua.globals());
+    let option = |name: &str, path: &str| -> LuaResult<LuaValue> {
+        match opts
+            .as_ref()
+            .map(|opts| opts.get::<LuaValue>(name))
+            .transpose()?
+        {
+            Some(LuaValue::Nil) | None => lookup_path(globals.clone(), &parse_path(path)),
+            Some(value) => Ok(value),
+        }
+    };
+
+    let LuaValue::Table(centers) = option("centers", "G.P_CENTERS")? else {
+        return Err(mlua::Error::RuntimeError(
+            "[INSOLENCE] Error: G.P_CENTERS is not available".into(),
+        ));
+    };
+    let localization = match option("localization", "G.localization")? {
+        LuaValue::Table(tbl) => Some(tbl),
+        _ => None,
+    };
+    let sets = match &opts {
+        Some(opts) => set_filter(opts.get::<LuaValue>("set")?)?,
+        None => None,
+    };
+
+    let mut scored = Vec::new();
+    for pair in centers.pairs::<LuaValue, LuaValue>() {
+        let (LuaValue::String(key), LuaValue::Table(center)) = pair? else {
+            continue;
+        };
+        let key = key.to_str()?.to_string();
+        let set = center.get::<Option<String>>("set")?.unwrap_or_default();
+
+        if let Some(sets) = &sets
+            && !sets.contains(&set.to_lowercase())
+        {
+            continue;
+        }
+
+        let name = match center_name(localization.as_ref(), &set, &key)? {
+            Some(name) => Some(name),
+            None => center.get::<Option<String>>("name")?,
+        };
+        let bare_key = key
+            .split_once('_')
+            .map_or(key.clone(), |(_, rest)| rest.replace('_', " "));
+
+        let score = [name.as_deref(), Some(key.as_str()), Some(bare_key.as_str())]
+            .into_iter()
+            .flatten()
+            .filter_map(|text| fuzzy_score(&query, text))
+            .fold(None, |best: Option<f64>, score| {
+                Some(best.map_or(score, |best| best.max(score)))
+            });
+
+        if let Some(score) = score {
+            scored.push((score, key, (name, set, center)));
+        }
+    }
+
+    let result = lua.create_table()?;
+    for (score, key, (name, set, center)) in search.rank(scored) {
+        let entry = lua.create_table()?;
+        entry.set("key", key)?;
+        entry.set("name", name)?;
+        entry.set("set", set)?;
+        entry.set("score", score)?;
+        entry.set("center", center)?;
+        result.raw_push(entry)?;
+    }
+
+    Ok(result)
+}
+
 #[macro_export]
 macro_rules! mkmodule {
     ($modname:ident, funcs [ $($func:ident),* $(,)? ]) => {